@@ -1,61 +1,63 @@
-use ggez::{Context, GameResult};
-use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, Mesh};
-use ggez::mint::Point2;
 use crate::config::*;
 use crate::functions::*;
 use crate::terrarium::Terrarium;
-use std::collections::HashMap;
+use crate::behaviour::*;
 use rand::Rng;
+use rand::rngs::StdRng;
 
 pub struct Ant {
 
+    pub id: u64,
+    caste: usize, // Index of behaviour in terrarium
     pub position: Position,
     nest_position: Position,
     path_positions: Vec<Position>,
     pub returning: bool, // Ant either digs tunnel or returns
-    carrying_food: bool, // Ant can return with or without food
-    soil_carried: i32,  // Progress toward carrying soil back to nest
-    visited_fields: Vec<Vec<bool>>,
+    pub carrying_food: bool, // Ant can return with or without food
+    pub soil_carried: i32,  // Progress toward carrying soil back to nest
+    visited_fields: VisitedSet,
     direction: f64,
+    pub traits: Traits, // Own alpha, beta and soil limit, inherited from a successful forager
 }
 
 impl Ant {
 
     // Creates new Ant
-    pub(crate) fn new(pos: &Position) -> Self {
+    pub(crate) fn new(id: u64, caste: usize, pos: &Position, rng: &mut StdRng) -> Self {
 
         // Randomise staring position and direction
-        let mut rng = rand::thread_rng();
         let random_direction = rng.gen_range(0.0..360.0);
         let rand_offset_x = rng.gen_range(-5.0..=5.0);
         let rand_offset_y = rng.gen_range(-5.0..=5.0);
         let starting_position = Position { x: pos.x + rand_offset_x as i32, y: pos.y + rand_offset_y as i32, };
 
         Ant {
+            id,
+            caste,
             position: starting_position,
             nest_position: pos.clone(),
             path_positions: Vec::new(),
             returning: false,
             carrying_food: false,
             soil_carried: 0,
-            visited_fields: vec![vec![false; HEIGHT]; WIDTH],
+            visited_fields: VisitedSet::default(),
             direction: random_direction,
+            traits: Traits::random(rng, TRAIT_SPREAD),
         }
 
     }
 
-    // Behaviour of ant
-    pub fn update(&mut self, terrarium: &mut Terrarium) {
+    // Behaviour of ant, first phase - decides where to go looking only at read-only terrarium
+    pub fn decide(&mut self, terrarium: &Terrarium, rng: &mut StdRng) -> Vec<Intent> {
+        let mut intents = Vec::new();
 
-        // Ant is just full of soil :(
-        if self.returning && !self.carrying_food {
-            self.go_back_one_move();
-            self.scan_for_target(terrarium);
-            return;
+        // Some ants sleep in the nest during night
+        if self.path_positions.is_empty() && !self.returning && terrarium.is_night() && self.rests_at_night() {
+            return intents;
         }
 
         // Starting position of ant
-        if self.path_positions.is_empty() {
+        if self.path_positions.is_empty() && !self.returning {
             let offset_x = self.direction.to_radians().cos();
             let offset_y = self.direction.to_radians().sin();
             self.position.x += offset_x as i32;
@@ -63,55 +65,84 @@ impl Ant {
 
             self.mark_visited();
             self.add_path();
-            return;
+            return intents;
         }
 
-        // Find neighbours
-        let neighbors: Vec<Position> = self.find_neighbours(terrarium)
-            .into_iter()
-            .filter(|&neighbour| { if self.carrying_food { terrarium.is_tunnel(neighbour) } else { true } })
-            .collect();
-
-        if neighbors.is_empty(){
-            self.go_back_one_move();
-            return;
+        // Caste behaviour decides, ant only checks whether the move is possible
+        let surroundings = self.surroundings(terrarium);
+        match terrarium.behaviour(self.caste).decide(&surroundings, rng) {
+            AntAction::Walk(pos) if surroundings.is_neighbour(pos) && terrarium.is_tunnel(pos) => self.step(terrarium, pos, &mut intents),
+            AntAction::Dig(pos) if surroundings.is_neighbour(pos) => self.step(terrarium, pos, &mut intents),
+            AntAction::Backtrack => self.go_back_one_move(),
+            _ => {}
         }
 
-        // Find best move
-        let probabilities = self.calculate_probabilities(terrarium, &neighbors);
-        if let Some(new_position) = self.select_next_position(probabilities) {
-            self.move_and_dig(terrarium, new_position);
-            self.mark_visited();
-            self.add_path();
+        // Ant carrying soil gets rid of it in dead ends on the way
+        if DUMP_IN_DEAD_ENDS && self.returning && !self.carrying_food && self.soil_carried > 0 {
+            if let Some(dead_end) = self.find_dead_end(terrarium) {
+                intents.push(Intent::Fill(dead_end));
+            }
         }
 
         // Evaluate move
-        self.scan_for_target(terrarium);
-        self.check_if_full();
+        self.scan_for_target(terrarium, &mut intents);
+        intents
     }
 
-    // Draw ant
-    pub(crate) fn draw(&self, ctx: &mut Context, canvas: &mut Canvas) -> GameResult {
+    // Behaviour of ant, second phase - applies decided changes to the terrarium
+    pub fn commit(&mut self, terrarium: &mut Terrarium, intents: Vec<Intent>) {
+        for intent in intents {
+            match intent {
+                Intent::Dig(pos) => self.dig(terrarium, pos),
+                Intent::Fill(pos) => {
+
+                    // Other ant could have filled it or dug further this tick
+                    if self.soil_carried > 0 && terrarium.is_dead_end(pos) {
+                        terrarium.fill_tunnel(pos.get_x_grid() as usize, pos.get_y_grid() as usize);
+                        self.soil_carried -= 1;
+                    }
+                }
+                Intent::TakeFood(index) => {
 
-        let point = Point2 { x: self.position.x as f32, y: self.position.y as f32, };
+                    // Other ant could have taken the last piece earlier in this tick
+                    if let Some(food) = terrarium.food_sources.get_mut(index) {
+                        if food.amount > 0 && !self.returning {
+                            self.found_food(food);
+                        }
+                    }
+                }
+                Intent::EnterNest => {
+                    if self.returning {
+                        self.found_nest(terrarium);
+                    }
+                }
+            }
+        }
 
-        let ant_hungry = Mesh::new_circle(ctx, DrawMode::fill(), point, ANT_SCALE, 0.1, Color::BLACK, )?;
-        // Ant carrying food
-        let ant_happy = Mesh::new_circle(ctx, DrawMode::fill(), point, ANT_SCALE, 0.1, Color::GREEN, )?;
-        // Ant carrying soil to the nest
-        let ant_heavy = Mesh::new_circle(ctx, DrawMode::fill(), point, ANT_SCALE, 0.1, Color::new(0.561, 0.361, 0.231, 1.0))?;
+        self.check_if_full(terrarium);
+    }
 
+    // Colour of ant for front ends
+    pub fn color(&self) -> (f32, f32, f32) {
         if self.carrying_food {
-            canvas.draw(&ant_happy, DrawParam::default());
+            (0.0, 1.0, 0.0) // Ant carrying food
+        }
+        else if self.returning {
+            (0.561, 0.361, 0.231) // Ant carrying soil to the nest
         }
-        else if self.soil_carried == ANT_SOIL_LIMIT {
-                canvas.draw(&ant_heavy, DrawParam::default());
-            }
         else {
-            canvas.draw(&ant_hungry, DrawParam::default());
+            (0.0, 0.0, 0.0) // Hungry ant
         }
+    }
 
-        Ok(())
+    // Runtime parameters with this ant's own alpha, beta and soil limit
+    pub fn params(&self, terrarium: &Terrarium) -> Params {
+        self.traits.apply(&terrarium.params)
+    }
+
+    // Decides once for all nights whether ant sleeps or works
+    fn rests_at_night(&self) -> bool {
+        (splitmix64(self.id) % 1000) as f32 / 1000.0 < NIGHT_REST_FRACTION
     }
 
     // Returns to nest from the memorised position
@@ -125,6 +156,25 @@ impl Ant {
         }
     }
 
+    // Side view only, ant buried by loose soil climbs on top of it, ant without support falls one field
+    pub fn apply_gravity(&mut self, terrarium: &Terrarium) {
+        let (x, mut y) = (self.position.get_x_grid(), self.position.get_y_grid());
+        if x < 0 || x >= WIDTH as i32 || y < 0 || y >= HEIGHT as i32 {
+            return;
+        }
+
+        while y > 0 && terrarium.mound.covers(x as usize, y as usize) {
+            y -= 1;
+        }
+        if y + 1 < HEIGHT as i32 && !terrarium.is_supported(x, y) {
+            y += 1;
+        }
+
+        if y != self.position.get_y_grid() {
+            self.position.y = y * WINDOW_TO_GAME_SCALE as i32;
+        }
+    }
+
     // Marks field as visited so ant won't come back here
     fn mark_visited(&mut self) {
 
@@ -133,7 +183,7 @@ impl Ant {
 
         // If field is valid, set it to visited
         if grid_x >= 0 && grid_x < WIDTH as i32 && grid_y >= 0 && grid_y < HEIGHT as i32 {
-            self.visited_fields[grid_x as usize][grid_y as usize] = true;
+            self.visited_fields.insert(grid_x, grid_y);
         }
     }
 
@@ -142,105 +192,97 @@ impl Ant {
         self.path_positions.push(self.position);
     }
 
-    // Finds non-visited neighbours
-    fn find_neighbours(&self, terrarium: &mut Terrarium) -> Vec<Position> {
-        let all_neighbours = terrarium.get_all_neighbors(self.position);
-        let mut valid_neighbours = Vec::new();
+    // Collects what ant sees around itself for its behaviour
+    fn surroundings(&self, terrarium: &Terrarium) -> Surroundings {
+        let (x, y) = (self.position.get_x_grid(), self.position.get_y_grid());
+        let mut neighbours = Vec::new();
 
-        for neighbour in all_neighbours {
+        for neighbour in terrarium.get_all_neighbors(self.position) {
             let (nx, ny) = (neighbour.get_x_grid(), neighbour.get_y_grid());
 
-            if nx >= 0 && nx < WIDTH as i32 && ny >= 0 && ny < HEIGHT as i32 &&
-                !self.visited_fields[nx as usize][ny as usize] {
-                valid_neighbours.push(neighbour);
+            if let Some((px, py)) = board_to_pheromones(x, y, nx, ny) {
+                neighbours.push(Neighbour {
+                    position: neighbour,
+                    tunnel: terrarium.is_tunnel(neighbour),
+                    rubble: terrarium.rubble[nx as usize][ny as usize],
+                    visited: self.visited_fields.contains(nx, ny),
+                    pheromone: terrarium.pheromones[px as usize][py as usize],
+                });
             }
         }
-        valid_neighbours
-    }
 
-    // Returns heuristics value
-    fn heuristics(terrarium: &Terrarium, pos: &Position) -> f32 {
-        if terrarium.is_tunnel(*pos) {
-            1.0
-        } else {
-            1.0 / DIGGING_COST
+        Surroundings {
+            id: self.id,
+            position: self.position,
+            nest: self.nest_position,
+            returning: self.returning,
+            carrying_food: self.carrying_food,
+            soil_carried: self.soil_carried,
+            neighbours,
+            food: terrarium.food_sources
+                .iter()
+                .filter(|food| food.amount > 0)
+                .map(|food| food.position)
+                .min_by_key(|food| (food.get_x_grid() - x).abs() + (food.get_y_grid() - y).abs()),
+            params: self.params(terrarium),
         }
     }
 
-    // Calculates probability for every path
-    fn calculate_probabilities(&self, terrarium: &Terrarium, neighbors: &Vec<Position>) -> HashMap<Position, f32> {
-        let mut desirabilities = HashMap::new();
-        let mut total_desire = 0.0;
-
-        for neighbor in neighbors {
-            if let Some((px, py)) = board_to_pheromones(self.position.get_x_grid(), self.position.get_y_grid(), neighbor.get_x_grid(), neighbor.get_y_grid()) {
-                let pheromone = terrarium.pheromones[px as usize][py as usize];
-                let heuristic = Ant::heuristics(terrarium, neighbor);
-                let desirability = pheromone.powf(DESIRABILITY_PHEROMONES) * heuristic.powf(DESIRABILITY_HEURISTICS);
-                desirabilities.insert(neighbor, desirability);
-                total_desire += desirability;
-            }
-        }
-
-        // Pack neighbours and desirabilities into one hashMap
-        let mut probabilities = HashMap::new();
-        for (&neighbor, &desirability) in &desirabilities {
-            probabilities.insert(*neighbor, desirability / total_desire);
-        }
-
-        probabilities
-    }
-
-    // Select next move probabilistically
-    fn select_next_position(&mut self, probabilities: HashMap<Position, f32>) -> Option<Position> {
-        let mut rng = rand::thread_rng();
-        let random_value: f64 = rng.gen(); // [0, 1]
-        let mut cumulative_probability = 0.0;
-
-        // Apply random value to hashmap
-        for (position, probability) in probabilities {
-            cumulative_probability += probability;
-            if random_value <= cumulative_probability.into() {
-                return Some(position)
-            }
-        }
-
-        None
+    // Moves to neighbouring field and marks it in memory
+    fn step(&mut self, terrarium: &Terrarium, pos: Position, intents: &mut Vec<Intent>) {
+        self.move_to(terrarium, pos, intents);
+        self.mark_visited();
+        self.add_path();
     }
 
     // Moves ant by its velocity, doesn't think whether it makes sense
-    fn move_and_dig(&mut self, terrarium: &mut Terrarium, pos: Position) {
+    fn move_to(&mut self, terrarium: &Terrarium, pos: Position, intents: &mut Vec<Intent>) {
 
         // Moves
         self.position = pos;
 
-        let (grid_x , grid_y) = (self.position.get_x_grid(), self.position.get_y_grid());
+        // Asks for digging tunnel
+        if !terrarium.is_tunnel(pos) {
+            intents.push(Intent::Dig(pos));
+        }
+    }
+
+    // Digs tunnel, unless other ant already dug it this tick
+    fn dig(&mut self, terrarium: &mut Terrarium, pos: Position) {
+        let (grid_x , grid_y) = (pos.get_x_grid(), pos.get_y_grid());
 
-        // Digs tunnel
-        if !terrarium.tunnels[grid_x as usize][grid_y as usize] {
-            terrarium.tunnels[grid_x as usize][grid_y as usize] = true;
+        if terrarium.dig_tunnel(grid_x as usize, grid_y as usize) {
             self.soil_carried += 1;
         }
     }
 
+    // Finds neighbouring dead end which isn't part of ant's own way back
+    fn find_dead_end(&self, terrarium: &Terrarium) -> Option<Position> {
+        terrarium.get_all_neighbors(self.position)
+            .into_iter()
+            .find(|&neighbour| {
+                !self.visited_fields.contains(neighbour.get_x_grid(), neighbour.get_y_grid()) && terrarium.is_dead_end(neighbour)
+            })
+    }
+
     // Looks for nest or food depends on context
-    fn scan_for_target(&mut self, terrarium: &mut Terrarium) {
-        if let Some(food) = terrarium.scan_for_food(self.position) {
+    fn scan_for_target(&self, terrarium: &Terrarium, intents: &mut Vec<Intent>) {
+        let mut taking_food = false;
+        if let Some(index) = terrarium.scan_for_food(self.position) {
             if !self.returning {
-                self.found_food(food);
+                intents.push(Intent::TakeFood(index));
+                taking_food = true;
             }
         }
 
-        if terrarium.scan_for_nest(self.position) {
-            if self.returning {
-                self.found_nest(terrarium);
-            }
+        if terrarium.scan_for_nest(self.position) && (self.returning || taking_food) {
+            intents.push(Intent::EnterNest);
         }
     }
 
     // Check if ant should be returning
-    fn check_if_full(&mut self) {
-        if self.soil_carried >= ANT_SOIL_LIMIT && !self.returning {
+    fn check_if_full(&mut self, terrarium: &Terrarium) {
+        if self.soil_carried >= self.params(terrarium).ant_soil_limit && !self.returning {
             self.returning = true;
         }
     }
@@ -252,7 +294,7 @@ impl Ant {
         }
         self.returning = true;
         self.carrying_food = true;
-        self.visited_fields = vec![vec![false; HEIGHT]; WIDTH];
+        self.visited_fields.clear();
         self.path_positions.clear();
     }
 
@@ -263,21 +305,29 @@ impl Ant {
         if self.carrying_food {
             self.spread_pheromones(terrarium);
             self.carrying_food = false;
-            terrarium.add_food();
+            terrarium.deliver_food(self.traits);
         }
 
+        // Rest of the soil goes to the mound
+        if self.soil_carried > 0 {
+            terrarium.dump_soil(self.id as usize, self.soil_carried);
+        }
+
+        // Helps with digging chambers while in the nest
+        terrarium.dig_chamber(self.id as usize);
+
         // Clears everything else
         self.position = self.nest_position;
         self.returning = false;
         self.soil_carried = 0;
-        self.visited_fields = vec![vec![false; HEIGHT]; WIDTH];
+        self.visited_fields.clear();
         self.path_positions.clear();
     }
 
     // Spreads pheromones at memorised locations (food -> nest)
     fn spread_pheromones(&mut self, terrarium: &mut Terrarium) {
 
-        let spread_value = PHEROMONES_INTENSITY / self.path_positions.len() as f32;
+        let spread_value = terrarium.params.pheromones_intensity / self.path_positions.len() as f32;
         while let Some(pos_1) = self.path_positions.pop() {
             if let Some(pos_2) = self.path_positions.last() {
                 if let Some((px, py)) = board_to_pheromones(pos_1.get_x_grid(), pos_1.get_y_grid(), pos_2.get_x_grid(), pos_2.get_y_grid()) {
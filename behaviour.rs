@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::io;
+use crate::config::NEURAL_WEIGHTS;
+use crate::functions::*;
+use crate::neural::NeuralBehavior;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+// One of the fields next to ant
+pub struct Neighbour {
+    pub position: Position,
+    pub tunnel: bool,
+    pub rubble: bool, // Collapsed tunnel, as easy to get through as open one
+    pub visited: bool,
+    pub pheromone: f32, // Pheromone on the edge between ant and this field
+}
+
+// Read-only view of what ant sees around itself
+pub struct Surroundings {
+    pub id: u64,
+    pub position: Position,
+    pub nest: Position,
+    pub returning: bool,
+    pub carrying_food: bool,
+    pub soil_carried: i32,
+    pub neighbours: Vec<Neighbour>,
+    pub food: Option<Position>, // Nearest food source which isn't empty
+    pub params: Params,
+}
+
+impl Surroundings {
+
+    // Checks if position is one of the neighbours
+    pub fn is_neighbour(&self, pos: Position) -> bool {
+        self.neighbours.iter().any(|neighbour| neighbour.position == pos)
+    }
+}
+
+// What ant decided to do in this tick
+pub enum AntAction {
+    Walk(Position), // Go through existing tunnel
+    Dig(Position),  // Go into soil, digging it
+    Backtrack,      // Go back one memorised move
+    Wait,
+}
+
+// Decision logic of ant, can be different for every caste
+pub trait AntBehavior: Send + Sync {
+    fn decide(&self, surroundings: &Surroundings, rng: &mut StdRng) -> AntAction;
+}
+
+// Returns built-in behaviour with given name, fails on unknown name or unreadable weights of neural ants
+pub fn behaviour_by_name(name: &str) -> io::Result<Box<dyn AntBehavior>> {
+    match name {
+        "aco" => Ok(Box::new(AcoBehavior)),
+        "random" => Ok(Box::new(RandomBehavior)),
+        "neural" => Ok(Box::new(NeuralBehavior::from_file_or_random(NEURAL_WEIGHTS)?)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown ant behaviour: {}", name))),
+    }
+}
+
+// Non-visited neighbours, ant with food walks only through tunnels
+pub(crate) fn candidates(surroundings: &Surroundings) -> Vec<&Neighbour> {
+    surroundings.neighbours
+        .iter()
+        .filter(|neighbour| !neighbour.visited)
+        .filter(|neighbour| { if surroundings.carrying_food { neighbour.tunnel } else { true } })
+        .collect()
+}
+
+// Walks or digs, depending on what is on given field
+pub(crate) fn step_into(neighbour: &Neighbour) -> AntAction {
+    if neighbour.tunnel { AntAction::Walk(neighbour.position) } else { AntAction::Dig(neighbour.position) }
+}
+
+// Default behaviour - ant colony optimization
+pub struct AcoBehavior;
+
+impl AcoBehavior {
+
+    // Returns heuristics value
+    fn heuristics(neighbour: &Neighbour, params: &Params) -> f32 {
+        if neighbour.tunnel || neighbour.rubble {
+            1.0
+        } else {
+            1.0 / params.digging_cost
+        }
+    }
+
+    // Calculates probability for every path, keeps order of neighbours so the choice is reproducible
+    fn calculate_probabilities<'a>(neighbours: &[&'a Neighbour], params: &Params) -> Vec<(&'a Neighbour, f32)> {
+        let mut desirabilities = Vec::new();
+        let mut total_desire = 0.0;
+
+        for &neighbour in neighbours {
+            let heuristic = AcoBehavior::heuristics(neighbour, params);
+            let desirability = neighbour.pheromone.powf(params.desirability_pheromones) * heuristic.powf(params.desirability_heuristics);
+            desirabilities.push((neighbour, desirability));
+            total_desire += desirability;
+        }
+
+        // Turn desirabilities into probabilities
+        desirabilities
+            .into_iter()
+            .map(|(neighbour, desirability)| (neighbour, desirability / total_desire))
+            .collect()
+    }
+
+    // Select next move probabilistically
+    fn select_next_position<'a>(probabilities: Vec<(&'a Neighbour, f32)>, rng: &mut StdRng) -> Option<&'a Neighbour> {
+        let random_value: f64 = rng.gen(); // [0, 1]
+        let mut cumulative_probability = 0.0;
+
+        // Apply random value to probabilities
+        for (neighbour, probability) in probabilities {
+            cumulative_probability += probability;
+            if random_value <= cumulative_probability.into() {
+                return Some(neighbour)
+            }
+        }
+
+        None
+    }
+}
+
+impl AntBehavior for AcoBehavior {
+    fn decide(&self, surroundings: &Surroundings, rng: &mut StdRng) -> AntAction {
+
+        // Ant is just full of soil :(
+        if surroundings.returning && !surroundings.carrying_food {
+            return AntAction::Backtrack;
+        }
+
+        // Find neighbours
+        let neighbours = candidates(surroundings);
+        if neighbours.is_empty() {
+            return AntAction::Backtrack;
+        }
+
+        // Find best move
+        let probabilities = AcoBehavior::calculate_probabilities(&neighbours, &surroundings.params);
+        match AcoBehavior::select_next_position(probabilities, rng) {
+            Some(neighbour) => step_into(neighbour),
+            None => AntAction::Wait,
+        }
+    }
+}
+
+// Move chosen by outside policy, order matches observations of the learning environment
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Move {
+    Left,
+    Right,
+    Up,
+    Down,
+    Stay,
+}
+
+impl Move {
+    pub const ALL: [Move; 5] = [Move::Left, Move::Right, Move::Up, Move::Down, Move::Stay];
+
+    pub fn offset(self) -> (i32, i32) {
+        match self {
+            Move::Left => (-1, 0),
+            Move::Right => (1, 0),
+            Move::Up => (0, -1),
+            Move::Down => (0, 1),
+            Move::Stay => (0, 0),
+        }
+    }
+}
+
+// Ants do what agents outside of the simulation chose for this tick, ants without move wait
+pub struct PolicyBehavior {
+    pub moves: HashMap<u64, Move>,
+}
+
+impl AntBehavior for PolicyBehavior {
+    fn decide(&self, surroundings: &Surroundings, _rng: &mut StdRng) -> AntAction {
+
+        // Carrying soil back isn't up to the agent
+        if surroundings.returning && !surroundings.carrying_food {
+            return AntAction::Backtrack;
+        }
+
+        let Some(&chosen) = self.moves.get(&surroundings.id) else {
+            return AntAction::Wait;
+        };
+        let (dx, dy) = chosen.offset();
+        let target = (surroundings.position.get_x_grid() + dx, surroundings.position.get_y_grid() + dy);
+
+        // Same rules as for other behaviours - only neighbours (so staying waits), ant with food doesn't dig
+        match surroundings.neighbours.iter().find(|neighbour| (neighbour.position.get_x_grid(), neighbour.position.get_y_grid()) == target) {
+            Some(neighbour) if neighbour.tunnel || !surroundings.carrying_food => step_into(neighbour),
+            _ => AntAction::Wait,
+        }
+    }
+}
+
+// Ignores pheromones, picks any non-visited neighbour
+pub struct RandomBehavior;
+
+impl AntBehavior for RandomBehavior {
+    fn decide(&self, surroundings: &Surroundings, rng: &mut StdRng) -> AntAction {
+        if surroundings.returning && !surroundings.carrying_food {
+            return AntAction::Backtrack;
+        }
+
+        let neighbours = candidates(surroundings);
+        if neighbours.is_empty() {
+            return AntAction::Backtrack;
+        }
+
+        step_into(neighbours[rng.gen_range(0..neighbours.len())])
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+use crate::config::*;
+use crate::functions::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChamberRole {
+    Storage, // Food brought by foragers
+    Brood,   // Larvae raised from stored food
+    Midden,  // Waste left after raising larvae
+}
+
+// Room next to the nest, holds things only in fields which are already dug
+pub struct Chamber {
+    pub role: ChamberRole,
+    pub passage: VecDeque<(usize, usize)>, // Corridor from nest still waiting to be dug, room is dug after it
+    pub fields: Vec<(usize, usize)>,
+    pub dug: usize,
+    pub stored: u32,
+}
+
+impl Chamber {
+    pub fn capacity(&self) -> u32 {
+        self.dug as u32 * CHAMBER_CAPACITY_PER_FIELD
+    }
+
+    pub fn has_room(&self) -> bool {
+        self.stored < self.capacity()
+    }
+}
+
+// Chambers of the colony and larvae growing in them
+pub struct Colony {
+    pub chambers: Vec<Chamber>,
+    pub planned: Vec<Vec<bool>>, // Fields belonging to some chamber or corridor leading to it
+    pub spilled: u32, // Food which didn't fit into storage
+    larvae: VecDeque<(u64, usize)>, // Tick the larva was laid and its brood chamber
+    next_slot: usize,
+}
+
+impl Colony {
+
+    // Plans first chamber of every role
+    pub fn new(nest: Position) -> Self {
+        let mut colony = Colony {
+            chambers: Vec::new(),
+            planned: vec![vec![false; HEIGHT]; WIDTH],
+            spilled: 0,
+            larvae: VecDeque::new(),
+            next_slot: 0,
+        };
+        for role in [ChamberRole::Storage, ChamberRole::Brood, ChamberRole::Midden] {
+            colony.plan(nest, role);
+        }
+        colony
+    }
+
+    // Finds place for new chamber, top-down around the nest, in side view under the surface on both sides of entrance
+    fn slot_centre(nest: Position, slot: usize) -> (i32, i32) {
+        let (nx, ny) = (nest.get_x_grid(), nest.get_y_grid());
+        let ring = (slot / 8) as i32;
+
+        if SIDE_VIEW {
+            let side = if slot.is_multiple_of(2) { -1 } else { 1 };
+            let depth = (slot / 2) as i32 * (CHAMBER_SIZE as i32 + 2);
+            (nx + side * CHAMBER_DISTANCE, ny + ENTRANCE_DEPTH as i32 + depth)
+        } else {
+            let angle = (slot % 8) as f32 * std::f32::consts::FRAC_PI_4 + ring as f32 * std::f32::consts::FRAC_PI_8;
+            let radius = (CHAMBER_DISTANCE + ring * 2 * CHAMBER_SIZE as i32) as f32;
+            (nx + (radius * angle.cos()) as i32, ny + (radius * angle.sin()) as i32)
+        }
+    }
+
+    // Plans new chamber of given role in the next free place
+    pub fn plan(&mut self, nest: Position, role: ChamberRole) -> bool {
+
+        // Places are tried in order until one fits into the terrarium
+        for _ in 0..64 {
+            let (cx, cy) = Colony::slot_centre(nest, self.next_slot);
+            self.next_slot += 1;
+
+            let half = CHAMBER_SIZE as i32 / 2;
+            let (x0, y0) = (cx - half, cy - half);
+            let top = if SIDE_VIEW { SURFACE_ROW as i32 } else { 0 };
+            if x0 < 0 || y0 < top || x0 + CHAMBER_SIZE as i32 > WIDTH as i32 || y0 + CHAMBER_SIZE as i32 > HEIGHT as i32 {
+                continue;
+            }
+
+            let mut fields = Vec::new();
+            for x in x0..x0 + CHAMBER_SIZE as i32 {
+                for y in y0..y0 + CHAMBER_SIZE as i32 {
+                    fields.push((x as usize, y as usize));
+                }
+            }
+
+            // Chambers mustn't overlap
+            if fields.iter().any(|&(x, y)| self.planned[x][y]) {
+                continue;
+            }
+
+            // Corridor goes from nest straight down (or up) and then sideways, so chamber is connected to tunnels
+            let (mut x, mut y) = (nest.get_x_grid(), nest.get_y_grid());
+            let mut passage = VecDeque::from([(x as usize, y as usize)]);
+            while (x, y) != (cx, cy) {
+                if y != cy { y += (cy - y).signum(); } else { x += (cx - x).signum(); }
+                passage.push_back((x as usize, y as usize));
+            }
+            passage.retain(|field| !fields.contains(field));
+
+            for &(x, y) in fields.iter().chain(&passage) {
+                self.planned[x][y] = true;
+            }
+            self.chambers.push(Chamber { role, passage, fields, dug: 0, stored: 0 });
+            return true;
+        }
+
+        false
+    }
+
+    // Next field some chamber still waits for, its corridor first
+    pub fn field_to_dig(&self) -> Option<(usize, usize)> {
+        self.chambers
+            .iter()
+            .find(|chamber| !chamber.passage.is_empty() || chamber.dug < chamber.fields.len())
+            .map(|chamber| chamber.passage.front().copied().unwrap_or_else(|| chamber.fields[chamber.dug]))
+    }
+
+    // Marks field returned by field_to_dig as dug
+    pub fn field_dug(&mut self) {
+        if let Some(chamber) = self.chambers.iter_mut().find(|chamber| !chamber.passage.is_empty() || chamber.dug < chamber.fields.len()) {
+            if chamber.passage.pop_front().is_none() {
+                chamber.dug += 1;
+            }
+        }
+    }
+
+    // Puts item to chamber of given role, plans new chamber when all of them are full
+    fn put(&mut self, nest: Position, role: ChamberRole) -> Option<usize> {
+        let index = self.chambers.iter().position(|chamber| chamber.role == role && chamber.has_room());
+        if let Some(index) = index {
+            self.chambers[index].stored += 1;
+        }
+
+        // Nothing planned of this role is waiting to be dug, so colony needs more room
+        let waiting = self.chambers.iter().any(|chamber| chamber.role == role && chamber.dug < chamber.fields.len());
+        if !waiting && !self.chambers.iter().any(|chamber| chamber.role == role && chamber.has_room()) {
+            self.plan(nest, role);
+        }
+
+        index
+    }
+
+    // Stores food brought by forager, food which doesn't fit is lost
+    pub fn store_food(&mut self, nest: Position) {
+        if self.put(nest, ChamberRole::Storage).is_none() {
+            self.spilled += 1;
+        }
+    }
+
+    // Takes food from storage chambers if there is enough of it
+    fn take_food(&mut self, amount: u32) -> bool {
+        if self.total(ChamberRole::Storage) < amount {
+            return false;
+        }
+
+        let mut left = amount;
+        for chamber in self.chambers.iter_mut().filter(|chamber| chamber.role == ChamberRole::Storage) {
+            let taken = chamber.stored.min(left);
+            chamber.stored -= taken;
+            left -= taken;
+        }
+        true
+    }
+
+    // Everything stored in chambers of given role
+    pub fn total(&self, role: ChamberRole) -> u32 {
+        self.chambers.iter().filter(|chamber| chamber.role == role).map(|chamber| chamber.stored).sum()
+    }
+
+    // Space of dug chambers of given role
+    pub fn capacity(&self, role: ChamberRole) -> u32 {
+        self.chambers.iter().filter(|chamber| chamber.role == role).map(|chamber| chamber.capacity()).sum()
+    }
+
+    // Lays larvae from stored food and returns how many grown ones hatch this tick
+    pub fn raise_brood(&mut self, nest: Position, tick: u64) -> usize {
+
+        // New larva if there is food and free room in brood chamber
+        if self.total(ChamberRole::Storage) >= FOOD_PER_LARVA {
+            if let Some(chamber) = self.put(nest, ChamberRole::Brood) {
+                self.take_food(FOOD_PER_LARVA);
+                self.larvae.push_back((tick, chamber));
+            }
+        }
+
+        // Grown larvae leave brood chamber and their waste goes to midden
+        let mut hatched = 0;
+        while let Some(&(laid, chamber)) = self.larvae.front() {
+            if tick < laid + LARVA_TICKS {
+                break;
+            }
+            self.larvae.pop_front();
+            self.chambers[chamber].stored -= 1;
+            for _ in 0..WASTE_PER_LARVA {
+                self.put(nest, ChamberRole::Midden);
+            }
+            hatched += 1;
+        }
+
+        hatched
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::*;
+
+// Part of the day from 0.0 (midnight) to 1.0, simulation starts in the morning
+pub fn time_of_day(tick: u64) -> f32 {
+    if DAY_LENGTH == 0 {
+        return 0.5;
+    }
+    ((tick % DAY_LENGTH) as f32 / DAY_LENGTH as f32 + DAY_START) % 1.0
+}
+
+// Height of the sun from -1.0 (midnight) to 1.0 (noon)
+pub fn daylight(tick: u64) -> f32 {
+    -(time_of_day(tick) * std::f32::consts::TAU).cos()
+}
+
+pub fn is_night(tick: u64) -> bool {
+    daylight(tick) < 0.0
+}
+
+// Temperature from 0.0 (coldest at midnight) to 1.0 (hottest at noon)
+pub fn temperature(tick: u64) -> f32 {
+    (1.0 + daylight(tick)) / 2.0
+}
+
+// Pheromones evaporate faster when it's warm, rate is the part of pheromone kept in one tick
+// Without the cycle configured rate stays as it is, scaled rate never makes pheromones negative
+pub fn evaporation_rate(rate: f32, tick: u64) -> f32 {
+    if DAY_LENGTH == 0 {
+        return rate;
+    }
+    let scale = NIGHT_EVAPORATION_SCALE + (DAY_EVAPORATION_SCALE - NIGHT_EVAPORATION_SCALE) * temperature(tick);
+    (1.0 - (1.0 - rate) * scale).clamp(0.0, 1.0)
+}
+
+// Day and hour for the HUD
+pub fn clock(tick: u64) -> String {
+    let day = tick.checked_div(DAY_LENGTH).unwrap_or(0) + 1;
+    let minutes = (time_of_day(tick) * 24.0 * 60.0) as u32;
+    format!("Day {} {:02}:{:02}", day, minutes / 60, minutes % 60)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // First tick at given time of day
+    fn tick_at(time: f32) -> u64 {
+        ((time - DAY_START).rem_euclid(1.0) * DAY_LENGTH as f32).round() as u64
+    }
+
+    #[test]
+    fn evaporation_follows_temperature() {
+        if DAY_LENGTH == 0 {
+            assert_eq!(evaporation_rate(0.9, 0), 0.9);
+            assert_eq!(evaporation_rate(0.9, 12345), 0.9);
+            return;
+        }
+        let (noon, midnight) = (evaporation_rate(0.9, tick_at(0.5)), evaporation_rate(0.9, tick_at(0.0)));
+        assert!((noon - (1.0 - 0.1 * DAY_EVAPORATION_SCALE)).abs() < 1e-4);
+        assert!((midnight - (1.0 - 0.1 * NIGHT_EVAPORATION_SCALE)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn evaporation_rate_stays_between_0_and_1() {
+        for rate in [0.0, 0.1, 0.3, 0.5, 0.99, 1.0] {
+            for tick in (0..20_000).step_by(100) {
+                let scaled = evaporation_rate(rate, tick);
+                assert!((0.0..=1.0).contains(&scaled), "rate {} at tick {} gave {}", rate, tick, scaled);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::functions::SoilType;
+
 // VISUAL/TECHNICAL
 pub const WIDTH: usize = 180;
 pub const HEIGHT: usize = 120;
@@ -9,17 +11,41 @@ pub const FOOD_SCALE: f32 = 30.0;
 pub const FOOD_DISTANCE: i32 = 10;
 pub const NEST_SIZE: f32 = 30.0;
 pub const PHEROMONE_SIZE: f32 = 2.0;
+pub const SIDE_VIEW: bool = false; // Vertical ant farm with sky on top instead of top-down plane
+pub const SURFACE_ROW: usize = 20; // Side view only, rows above are sky
+pub const ENTRANCE_DEPTH: usize = 3; // Side view only, shaft dug below nest at start
+pub const MAX_SPEED: u32 = 64; // Most ticks per frame
+pub const TUI_FRAME_MS: u64 = 33; // Time between frames of terminal front end
+pub const THREAD_COUNT: usize = 0; // 0 lets rayon pick number of threads
 
 
 
 // GAMEPLAY VARIABLES
 pub const UNLIMITED_FOOD: bool = false;
 pub const ANT_SOIL_LIMIT: i32 = 100;
+pub const DUMP_SITES: &[(i32, i32)] = &[(-10, -6), (10, 6)]; // Grid offsets from nest where soil is piled
+pub const DUMP_IN_DEAD_ENDS: bool = true; // Returning ants fill dead-end tunnels on the way
+pub const DEAD_END_MIN_AGE: u64 = 100; // Ticks since an ant was in dead end before it can be filled, tips being dug stay open
+pub const MOUND_REPOSE: u32 = 2; // Highest step in the mound before soil rolls down
+pub const STABILITY_INTERVAL: u64 = 50; // Ticks between checks for collapsing and decaying tunnels
+pub const TUNNEL_DECAY_TICKS: u64 = 5000; // Tunnel nobody walked through for so long fills with soil, 0 turns it off
+pub const COLLAPSE_KILL_CHANCE: f32 = 0.2; // Ant in collapsing tunnel dies, otherwise it's trapped and digs out
+pub const SOIL_PATCH_SIZE: usize = 12; // Top-down only, size of patches of one soil type (layers in side view)
+// Ants dig wide open areas with many unstable fields, chances stay small so collapses don't keep burying trails
+pub const SOIL_TYPES: &[SoilType] = &[
+    SoilType { name: "loam", max_open: 20, max_overburden: 40, collapse_chance: 0.0001, drain_rate: 0.004, color: (0.22, 0.15, 0.13) },
+    SoilType { name: "sand", max_open: 16, max_overburden: 20, collapse_chance: 0.0005, drain_rate: 0.01, color: (0.30, 0.22, 0.15) },
+    SoilType { name: "clay", max_open: 22, max_overburden: 80, collapse_chance: 0.00002, drain_rate: 0.001, color: (0.26, 0.13, 0.10) },
+];
+pub const MAX_ANTS: usize = 1000; // Larvae stop hatching when colony is this big
+pub const EVENT_LOG_SIZE: usize = 100;
+pub const EVENT_LOG_LINES: usize = 5; // Newest events shown on screen
 pub const NEST_DETECTION_RANGE: i32 = 25;
 pub const FOOD_DETECTION_RANGE: i32 = 5;
 pub const ANT_COUNT: usize = 100;
 pub const FOOD_SOURCES_COUNT: usize = 3;
 pub const FOOD_AMOUNT_PER_SOURCE: usize = 50;
+pub const SEED: u64 = 42;
 
 
 
@@ -32,3 +58,132 @@ pub const DESIRABILITY_HEURISTICS: f32 = 2.0;
 pub const MIN_PHEROMONES: f32 = 1.0;
 pub const MAX_PHEROMONES: f32 = 2000.0;
 pub const DIGGING_COST: f32 = 100.0;
+pub const OPTIMALITY_INTERVAL: u64 = 100; // Ticks between comparisons of pheromone trails with shortest paths, 0 turns it off
+pub const CASTES: &[(&str, f32)] = &[("aco", 1.0)]; // Behaviour of every caste and its share of ants ("aco", "random", "neural")
+
+
+
+// NEURAL ANTS (caste "neural", --evolve)
+pub const NEURAL_HIDDEN: &[usize] = &[8]; // Sizes of hidden layers, none of them 0
+pub const NEURAL_WEIGHTS: &str = "neural_weights.txt"; // Loaded by neural ants if it exists, best network of --evolve is saved here
+pub const NEURAL_TEMPERATURE: f32 = 0.5; // Lower makes ants follow the network's preferences more strictly
+pub const NEURAL_POPULATION: usize = 16;
+pub const NEURAL_GENERATIONS: usize = 20;
+pub const NEURAL_ELITE: usize = 4; // Best networks kept, others are their mutated copies
+pub const NEURAL_MUTATION_SIZE: f32 = 0.2; // Largest change of one weight
+
+
+
+// HERITABLE TRAITS
+pub const TRAIT_SPREAD: f32 = 0.2; // Alpha, beta and soil limit of founding ants differ from parameters by up to this fraction
+pub const TRAIT_MUTATION: f32 = 0.05; // Largest change of every trait passed from forager to larva, as fraction
+pub const TRAIT_POOL: usize = 50; // Larvae inherit traits of one of this many latest successful foragers
+
+
+
+// NEST CHAMBERS
+pub const CHAMBER_SIZE: usize = 4;
+pub const CHAMBER_DISTANCE: i32 = 10; // Grid fields between nest and first chambers
+pub const CHAMBER_CAPACITY_PER_FIELD: u32 = 2;
+pub const FOOD_PER_LARVA: u32 = 3;
+pub const LARVA_TICKS: u64 = 1000; // Time for larva to grow into ant
+pub const WASTE_PER_LARVA: u32 = 1;
+
+
+
+// WEATHER
+pub const RAIN_SCHEDULE: &[(u64, u64, f32)] = &[]; // Start tick, duration and water added per tick of planned rains
+pub const RAIN_CHANCE: f32 = 0.0005; // Chance of random rain starting in a tick
+pub const RAIN_DURATION: u64 = 200;
+pub const RAIN_INTENSITY: f32 = 0.05;
+pub const RAIN_REACH: i32 = 15; // Fields from nest (top-down) or below surface (side view) rain gets to
+
+
+
+// DAY AND NIGHT
+pub const DAY_LENGTH: u64 = 6000; // Ticks in one day, 0 turns the cycle off
+pub const DAY_START: f32 = 0.25; // Simulation starts at 6:00
+pub const NIGHT_REST_FRACTION: f32 = 0.5; // Ants staying in the nest during night
+pub const DAY_EVAPORATION_SCALE: f32 = 1.5; // Evaporation speed at noon compared to configured rates
+pub const NIGHT_EVAPORATION_SCALE: f32 = 0.5; // Evaporation speed at midnight
+pub const DIURNAL_FOOD_SHARE: f32 = 0.3; // Food sources available only during day
+
+
+// RECORDING
+pub const RECORD_EVERY: u64 = 5; // Every Nth rendered frame is saved (every Nth tick in headless mode)
+pub const RECORD_DIR: &str = "recordings"; // Every recording gets its own numbered directory inside
+pub const RECORD_GIF: bool = false; // Encode one animated GIF instead of PNG sequence (also --gif)
+pub const RECORD_SCALE: u32 = 4; // Pixels per grid field in saved images
+pub const GIF_FRAME_DELAY_MS: u32 = 40;
+pub const HEADLESS_TICKS: u64 = 10000; // Longest run without any window (--headless), 0 means no limit
+
+
+
+// STOP CONDITIONS (headless only)
+pub const STOP_CHECK_INTERVAL: u64 = 100; // Ticks between checks of conditions below
+pub const STOP_WHEN_FOOD_GONE: bool = true;
+pub const DELIVERY_RATE_WINDOW: u64 = 1000; // Ticks over which food delivery rate is averaged
+pub const DELIVERY_STABLE_TICKS: u64 = 0; // Stop when delivery rate stays within epsilon this long, 0 turns it off
+pub const DELIVERY_EPSILON: f32 = 0.002; // Food per tick
+pub const STAGNATION_ENTROPY: f32 = 0.0; // Trails stagnate when pheromone entropy falls below, 0 turns it off
+pub const RESET_ON_STAGNATION: bool = false; // Reset pheromones on stagnation and go on instead of stopping (MMAS)
+pub const MAX_WALL_CLOCK_SECONDS: f32 = 0.0; // 0 turns it off
+
+
+
+// CONTROL SERVER (--server)
+pub const SERVER_ADDRESS: &str = "127.0.0.1:7878";
+pub const SERVER_TIMEOUT_MS: u64 = 5000; // Request fails if simulation doesn't answer in time
+pub const SERVER_MAX_STEP: u64 = 1000; // Most ticks one /step request runs, longer ones would stall the simulation
+pub const SERVER_PHEROMONE_DELTA: f32 = 1.0; // Smaller pheromone changes aren't streamed
+
+
+
+// EXPORT
+pub const EXPORT_DIR: &str = "exports";
+pub const EXPORT_GRAPH_FORMAT: &str = "graphml"; // "graphml" or "dot"
+pub const EXPORT_JUNCTIONS_ONLY: bool = true; // Corridors become single edges, otherwise every tunnel field is a node
+
+
+
+// LEARNING ENVIRONMENT
+pub const ENV_EPISODE_TICKS: u64 = 2000; // Episode is cut off after this many ticks
+pub const ENV_VIEW_RADIUS: usize = 2; // Ant sees tunnels in square of this radius around itself, 0 only its neighbours
+pub const ENV_REWARD_FOOD_FOUND: f32 = 0.5;
+pub const ENV_REWARD_FOOD_DELIVERED: f32 = 1.0;
+pub const ENV_REWARD_STEP: f32 = -0.001; // Every ant in every tick, so agents don't dawdle
+
+
+
+// EXPERIMENT PRESETS (--preset)
+pub const BRIDGE_STEM: i32 = 10; // Corridor from nest to fork and from join to food
+pub const BRIDGE_SPAN: i32 = 20; // Distance between fork and join
+pub const BRIDGE_DEPTH_SHORT: i32 = 4; // Rows branch bends away from straight line
+pub const BRIDGE_DEPTH_LONG: i32 = 16;
+pub const BRIDGE_SHORT_LATER_TICK: u64 = 3000; // Short branch is dug in this tick in "double_bridge_short_later"
+pub const BRIDGE_FOOD_AMOUNT: usize = 100000;
+pub const BRIDGE_SAMPLE_INTERVAL: u64 = 100; // Ticks in one window of branch traffic
+pub const DENEUBOURG_K: f32 = 20.0; // Attraction of unmarked branch in Deneubourg's model
+pub const DENEUBOURG_N: f32 = 2.0; // Nonlinearity of choice
+
+
+
+// PARAMETER TUNING (--tune)
+pub const TUNE_FITNESS: &str = "food_per_tick"; // "food_per_tick" or "food_per_soil"
+pub const TUNE_POPULATION: usize = 16;
+pub const TUNE_GENERATIONS: usize = 20;
+pub const TUNE_ELITE: usize = 2; // Best candidates copied unchanged into next generation
+pub const TUNE_TOURNAMENT: usize = 3; // Candidates competing for being a parent
+pub const TUNE_MUTATION_RATE: f32 = 0.3; // Chance of every parameter to mutate
+pub const TUNE_MUTATION_SIZE: f32 = 0.1; // Largest mutation as part of parameter range
+pub const TUNE_SEEDS: u64 = 3; // Headless runs with different worlds averaged for every candidate
+pub const TUNE_TICKS: u64 = 3000; // Length of one run, shorter if food runs out
+pub const TUNE_DIR: &str = "tuning";
+pub const TUNE_RANGES: &[(&str, f32, f32)] = &[
+    ("pheromones_intensity", 1000.0, 50000.0),
+    ("evaporation_rate_fast", 0.5, 0.99),
+    ("evaporation_rate_slow", 0.9, 0.999),
+    ("desirability_pheromones", 0.0, 10.0),
+    ("desirability_heuristics", 0.0, 10.0),
+    ("ant_soil_limit", 10.0, 300.0),
+];
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::io;
+use crate::config::*;
+use crate::ants::Ant;
+use crate::behaviour::{Move, PolicyBehavior};
+use crate::terrarium::Terrarium;
+
+// Parts of what every ant observes, in this order
+#[derive(Clone, Copy)]
+pub struct ObservationConfig {
+    pub pheromones: bool, // Pheromone on edges to left, right, upper and lower neighbour, 0 to 1
+    pub moves: bool, // Same four neighbours - 1 tunnel, 0.5 soil to dig, 0 can't enter
+    pub radius: usize, // Square of fields around ant - 1 tunnel, 0 soil, -1 blocked, 0 turns it off
+    pub food: bool, // Direction to nearest food and distance, both scaled
+    pub nest: bool, // Direction to nest and distance
+    pub state: bool, // Carrying food, returning and soil carried compared to limit
+}
+
+impl Default for ObservationConfig {
+    fn default() -> Self {
+        ObservationConfig { pheromones: true, moves: true, radius: ENV_VIEW_RADIUS, food: true, nest: true, state: true }
+    }
+}
+
+impl ObservationConfig {
+
+    // Number of values in one observation
+    pub fn size(&self) -> usize {
+        let side = 2 * self.radius + 1;
+        4 * self.pheromones as usize + 4 * self.moves as usize + if self.radius > 0 { side * side } else { 0 }
+            + 3 * self.food as usize + 3 * self.nest as usize + 3 * self.state as usize
+    }
+}
+
+// Result of one step for one ant
+pub struct AgentStep {
+    pub id: u64,
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    pub done: bool, // Ant died or episode ended
+}
+
+// Gym-style environment - agents choose moves of ants instead of their behaviours, one step is one tick
+pub struct Environment {
+    pub terrarium: Terrarium,
+    pub config: ObservationConfig,
+}
+
+// Field as seen in the view square
+fn field_value(terrarium: &Terrarium, x: i32, y: i32) -> f32 {
+    if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 ||
+        terrarium.mound.covers(x as usize, y as usize) || terrarium.obstacles[x as usize][y as usize] {
+        return -1.0;
+    }
+    if terrarium.tunnels[x as usize][y as usize] || terrarium.is_sky(x, y) { 1.0 } else { 0.0 }
+}
+
+// Unit direction and scaled distance to given grid field, zeros if there is none
+fn direction(from: (i32, i32), to: Option<(i32, i32)>) -> [f32; 3] {
+    let Some(to) = to else {
+        return [0.0; 3];
+    };
+    let (dx, dy) = ((to.0 - from.0) as f32, (to.1 - from.1) as f32);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 {
+        return [0.0; 3];
+    }
+    [dx / distance, dy / distance, distance / (WIDTH + HEIGHT) as f32]
+}
+
+impl Environment {
+
+    pub fn new(config: ObservationConfig) -> io::Result<Environment> {
+        let mut environment = Environment { terrarium: Terrarium::new()?, config };
+        environment.hand_over(HashMap::new());
+        Ok(environment)
+    }
+
+    // Starts new episode in world of given seed, parameters set before stay
+    pub fn reset(&mut self, seed: u64) -> io::Result<Vec<AgentStep>> {
+        let params = self.terrarium.params;
+        self.terrarium = Terrarium::with_seed(seed)?;
+        self.terrarium.params = params;
+        self.hand_over(HashMap::new());
+
+        Ok(self.terrarium.ants
+            .iter()
+            .map(|ant| AgentStep { id: ant.id, observation: self.observe(ant), reward: 0.0, done: false })
+            .collect())
+    }
+
+    // Every caste follows the moves of agents
+    fn hand_over(&mut self, moves: HashMap<u64, Move>) {
+        for caste in 0..CASTES.len() {
+            self.terrarium.set_behaviour(caste, Box::new(PolicyBehavior { moves: moves.clone() }));
+        }
+    }
+
+    // Episode ends on time limit or when all food is eaten
+    pub fn is_finished(&self) -> bool {
+        self.terrarium.tick >= ENV_EPISODE_TICKS || (!UNLIMITED_FOOD && self.terrarium.food_sources.iter().all(|food| food.amount == 0))
+    }
+
+    // Runs one tick with given moves, returns every ant which acted or hatched, dead ones with empty observation
+    pub fn step(&mut self, moves: HashMap<u64, Move>) -> Vec<AgentStep> {
+        let before: HashMap<u64, bool> = self.terrarium.ants.iter().map(|ant| (ant.id, ant.carrying_food)).collect();
+
+        self.hand_over(moves);
+        self.terrarium.step();
+        let finished = self.is_finished();
+
+        let mut steps: Vec<AgentStep> = self.terrarium.ants
+            .iter()
+            .map(|ant| {
+                let reward = match before.get(&ant.id) {
+                    Some(&carried) if !carried && ant.carrying_food => ENV_REWARD_STEP + ENV_REWARD_FOOD_FOUND,
+                    Some(&carried) if carried && !ant.carrying_food => ENV_REWARD_STEP + ENV_REWARD_FOOD_DELIVERED,
+                    Some(_) => ENV_REWARD_STEP,
+                    None => 0.0, // Hatched this tick
+                };
+                AgentStep { id: ant.id, observation: self.observe(ant), reward, done: finished }
+            })
+            .collect();
+
+        // Buried in collapsed tunnel, sorted so the order doesn't depend on hashing
+        let mut dead: Vec<u64> = before.keys().copied().filter(|&id| !self.terrarium.ants.iter().any(|ant| ant.id == id)).collect();
+        dead.sort_unstable();
+        steps.extend(dead.into_iter().map(|id| AgentStep { id, observation: Vec::new(), reward: 0.0, done: true }));
+        steps
+    }
+
+    // What ant sees, length is config.size()
+    pub fn observe(&self, ant: &Ant) -> Vec<f32> {
+        let terrarium = &self.terrarium;
+        let (x, y) = (ant.position.get_x_grid(), ant.position.get_y_grid());
+        let mut observation = Vec::with_capacity(self.config.size());
+
+        let neighbours = terrarium.get_all_neighbors(ant.position);
+        let enterable = |(dx, dy): (i32, i32)| neighbours.iter().find(|n| (n.get_x_grid(), n.get_y_grid()) == (x + dx, y + dy)).copied();
+
+        if self.config.pheromones {
+            for chosen in &Move::ALL[..4] {
+                let (dx, dy) = chosen.offset();
+                let pheromone = if enterable((dx, dy)).is_some() { terrarium.pheromone_between(x, y, x + dx, y + dy) } else { 0.0 };
+                observation.push((pheromone / MAX_PHEROMONES).min(1.0));
+            }
+        }
+
+        if self.config.moves {
+            for chosen in &Move::ALL[..4] {
+                observation.push(match enterable(chosen.offset()) {
+                    Some(neighbour) if terrarium.is_tunnel(neighbour) => 1.0,
+                    Some(_) => 0.5,
+                    None => 0.0,
+                });
+            }
+        }
+
+        if self.config.radius > 0 {
+            let radius = self.config.radius as i32;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    observation.push(field_value(terrarium, x + dx, y + dy));
+                }
+            }
+        }
+
+        if self.config.food {
+            let nearest = terrarium.food_sources
+                .iter()
+                .filter(|food| food.amount > 0)
+                .map(|food| (food.position.get_x_grid(), food.position.get_y_grid()))
+                .min_by_key(|&(fx, fy)| (fx - x).abs() + (fy - y).abs());
+            observation.extend(direction((x, y), nearest));
+        }
+
+        if self.config.nest {
+            observation.extend(direction((x, y), Some((terrarium.nest.get_x_grid(), terrarium.nest.get_y_grid()))));
+        }
+
+        if self.config.state {
+            observation.push(ant.carrying_food as u8 as f32);
+            observation.push(ant.returning as u8 as f32);
+            observation.push(ant.soil_carried as f32 / ant.params(terrarium).ant_soil_limit as f32);
+        }
+
+        observation
+    }
+}
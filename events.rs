@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+use std::fmt;
+use crate::config::*;
+
+// Things worth noting which happened in the terrarium
+pub enum Event {
+    Collapse { x: usize, y: usize, soil: &'static str, killed: usize },
+    RainStarted { duration: u64, intensity: f32 },
+    RainStopped,
+    Exported { path: String },
+    PheromonesReset { entropy: f32 },
+    Scenario { action: String },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Collapse { x, y, soil, killed } => write!(f, "Tunnel in {} at ({}, {}) collapsed, {} ants died", soil, x, y, killed),
+            Event::RainStarted { duration, intensity } => write!(f, "Rain started for {} ticks (intensity {})", duration, intensity),
+            Event::RainStopped => write!(f, "Rain stopped"),
+            Event::Exported { path } => write!(f, "Tunnels exported to {}", path),
+            Event::PheromonesReset { entropy } => write!(f, "Trails stagnated (entropy {:.3}), pheromones reset", entropy),
+            Event::Scenario { action } => write!(f, "Scenario: {}", action),
+        }
+    }
+}
+
+// Newest events with ticks they happened in
+pub struct EventLog {
+    entries: VecDeque<(u64, Event)>,
+}
+
+impl EventLog {
+
+    pub fn new() -> Self {
+        EventLog { entries: VecDeque::new() }
+    }
+
+    // Adds event, forgets the oldest one if log is full
+    pub fn push(&mut self, tick: u64, event: Event) {
+        if self.entries.len() == EVENT_LOG_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((tick, event));
+    }
+
+    // Newest events as text, oldest first
+    pub fn latest(&self, count: usize) -> Vec<String> {
+        self.entries
+            .iter()
+            .skip(self.entries.len().saturating_sub(count))
+            .map(|(tick, event)| format!("[{}] {}", tick, event))
+            .collect()
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::config::*;
+use crate::terrarium::Terrarium;
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// Tunnel network as graph - nodes are tunnel fields (or only junctions and dead ends), edges corridors between them
+pub struct TunnelGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+pub struct GraphNode {
+    pub x: usize,
+    pub y: usize,
+    pub kind: &'static str, // "nest", "junction", "dead_end" or "tunnel"
+}
+
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub length: u32, // Steps between both nodes
+    pub pheromone: f32, // Average pheromone on the corridor
+}
+
+// Pheromone on edge between two neighbouring fields
+fn pheromone_between(terrarium: &Terrarium, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> f32 {
+    terrarium.pheromone_between(x1 as i32, y1 as i32, x2 as i32, y2 as i32)
+}
+
+// Field is inside the grid and dug (sky in side view doesn't count)
+fn is_dug(terrarium: &Terrarium, x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT && terrarium.tunnels[x as usize][y as usize]
+}
+
+// Neighbouring fields which are dug
+fn tunnel_neighbours(terrarium: &Terrarium, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
+    DIRECTIONS
+        .iter()
+        .map(|&(dx, dy)| (x as i32 + dx, y as i32 + dy))
+        .filter(|&(nx, ny)| is_dug(terrarium, nx, ny))
+        .map(|(nx, ny)| (nx as usize, ny as usize))
+        .collect()
+}
+
+impl TunnelGraph {
+
+    // Builds graph from tunnels, with junctions_only corridors are merged into single edges
+    pub fn new(terrarium: &Terrarium, junctions_only: bool) -> TunnelGraph {
+        let nest = (terrarium.nest.get_x_grid() as usize, terrarium.nest.get_y_grid() as usize);
+        let mut graph = TunnelGraph { nodes: Vec::new(), edges: Vec::new() };
+        let mut ids: HashMap<(usize, usize), usize> = HashMap::new();
+
+        let fields: Vec<(usize, usize)> = (0..WIDTH)
+            .flat_map(|x| (0..HEIGHT).map(move |y| (x, y)))
+            .filter(|&(x, y)| terrarium.tunnels[x][y])
+            .collect();
+
+        // Field is a node if it's the nest or anything else than a plain corridor
+        for &field in &fields {
+            let degree = tunnel_neighbours(terrarium, field).len();
+            let kind = match degree {
+                _ if field == nest => "nest",
+                0 | 1 => "dead_end",
+                2 => "tunnel",
+                _ => "junction",
+            };
+            if !junctions_only || kind != "tunnel" {
+                ids.insert(field, graph.nodes.len());
+                graph.nodes.push(GraphNode { x: field.0, y: field.1, kind });
+            }
+        }
+
+        if !junctions_only {
+            for &field in &fields {
+                for neighbour in tunnel_neighbours(terrarium, field) {
+                    if ids[&field] < ids[&neighbour] {
+                        let pheromone = pheromone_between(terrarium, field, neighbour);
+                        graph.edges.push(GraphEdge { from: ids[&field], to: ids[&neighbour], length: 1, pheromone });
+                    }
+                }
+            }
+            return graph;
+        }
+
+        // Walk every corridor from its node until another node is found
+        let mut walked: HashSet<(usize, usize)> = HashSet::new();
+        let mut start = 0;
+        loop {
+            for id in start..graph.nodes.len() {
+                let node = (graph.nodes[id].x, graph.nodes[id].y);
+
+                for next in tunnel_neighbours(terrarium, node) {
+                    if walked.contains(&next) || (ids.contains_key(&next) && ids[&next] <= id) {
+                        continue; // Corridor already walked from its other end
+                    }
+
+                    let (mut previous, mut current) = (node, next);
+                    let mut length = 1;
+                    let mut pheromone = pheromone_between(terrarium, previous, current);
+
+                    while !ids.contains_key(&current) {
+                        walked.insert(current);
+                        let Some(following) = tunnel_neighbours(terrarium, current).into_iter().find(|&field| field != previous) else {
+                            break;
+                        };
+                        pheromone += pheromone_between(terrarium, current, following);
+                        (previous, current) = (current, following);
+                        length += 1;
+                    }
+
+                    graph.edges.push(GraphEdge { from: id, to: ids[&current], length, pheromone: pheromone / length as f32 });
+                }
+            }
+
+            // Loops without any junction get one node, so they aren't lost
+            start = graph.nodes.len();
+            match fields.iter().find(|field| !ids.contains_key(field) && !walked.contains(field)) {
+                Some(&field) => {
+                    ids.insert(field, graph.nodes.len());
+                    graph.nodes.push(GraphNode { x: field.0, y: field.1, kind: "tunnel" });
+                }
+                None => return graph,
+            }
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph tunnels {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            writeln!(dot, "  {} [kind=\"{}\", pos=\"{},{}!\"];", id, node.kind, node.x, HEIGHT - 1 - node.y).unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(dot, "  {} -- {} [length={}, weight={:.3}];", edge.from, edge.to, edge.length, edge.pheromone).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"int\"/>\n",
+            "  <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"int\"/>\n",
+            "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"int\"/>\n",
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>\n",
+            "  <graph id=\"tunnels\" edgedefault=\"undirected\">\n",
+        ));
+        for (id, node) in self.nodes.iter().enumerate() {
+            writeln!(
+                xml,
+                "    <node id=\"n{}\"><data key=\"x\">{}</data><data key=\"y\">{}</data><data key=\"kind\">{}</data></node>",
+                id, node.x, node.y, node.kind
+            ).unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                xml,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"length\">{}</data><data key=\"weight\">{}</data></edge>",
+                edge.from, edge.to, edge.length, edge.pheromone
+            ).unwrap();
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+}
+
+// Colour as SVG hex code
+fn hex((r, g, b): (f32, f32, f32)) -> String {
+    format!("#{:02x}{:02x}{:02x}", (r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+// Tunnels as vector image in window coordinates, pheromones as strokes getting wider and brighter
+pub fn tunnels_to_svg(terrarium: &Terrarium) -> String {
+    let scale = WINDOW_TO_GAME_SCALE;
+    let mut svg = String::new();
+    writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">", WINDOW_WIDTH, WINDOW_HEIGHT).unwrap();
+    writeln!(svg, "  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", hex(SOIL_TYPES[0].color)).unwrap();
+
+    // Dug fields
+    svg.push_str("  <g fill=\"#663319\">\n");
+    for x in 0..WIDTH {
+        for y in (0..HEIGHT).filter(|&y| terrarium.tunnels[x][y]) {
+            writeln!(svg, "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>", x as f32 * scale, y as f32 * scale, scale, scale).unwrap();
+        }
+    }
+    svg.push_str("  </g>\n");
+
+    // Pheromone edges between centres of neighbouring tunnels
+    svg.push_str("  <g stroke=\"#ffffff\" stroke-linecap=\"round\">\n");
+    for x in 0..WIDTH {
+        for y in (0..HEIGHT).filter(|&y| terrarium.tunnels[x][y]) {
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if !is_dug(terrarium, nx as i32, ny as i32) {
+                    continue;
+                }
+                let pheromone = pheromone_between(terrarium, (x, y), (nx, ny));
+                if pheromone <= MIN_PHEROMONES {
+                    continue;
+                }
+                let strength = (pheromone / MAX_PHEROMONES).min(1.0);
+                writeln!(
+                    svg,
+                    "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"{:.2}\" stroke-opacity=\"{:.2}\"/>",
+                    (x as f32 + 0.5) * scale, (y as f32 + 0.5) * scale, (nx as f32 + 0.5) * scale, (ny as f32 + 0.5) * scale,
+                    0.5 + strength * scale, 0.2 + 0.8 * strength
+                ).unwrap();
+            }
+        }
+    }
+    svg.push_str("  </g>\n");
+
+    // Nest and food
+    writeln!(svg, "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#24171a\"/>", terrarium.nest.x as f32 - NEST_SIZE / 2.0, terrarium.nest.y as f32 - NEST_SIZE / 2.0, NEST_SIZE, NEST_SIZE).unwrap();
+    for food in terrarium.food_sources.iter().filter(|food| food.amount > 0) {
+        writeln!(svg, "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#00ff00\"/>", food.position.x as f32 - FOOD_SCALE / 2.0, food.position.y as f32 - FOOD_SCALE / 2.0, FOOD_SCALE, FOOD_SCALE).unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Writes SVG and graph of current tunnels and history of trail quality into the export directory, returns path without extension
+pub fn export(terrarium: &Terrarium) -> io::Result<PathBuf> {
+    fs::create_dir_all(EXPORT_DIR)?;
+    let path = PathBuf::from(EXPORT_DIR).join(format!("tunnels_{:07}", terrarium.tick));
+
+    fs::write(path.with_extension("svg"), tunnels_to_svg(terrarium))?;
+
+    let graph = TunnelGraph::new(terrarium, EXPORT_JUNCTIONS_ONLY);
+    match EXPORT_GRAPH_FORMAT {
+        "dot" => fs::write(path.with_extension("dot"), graph.to_dot())?,
+        _ => fs::write(path.with_extension("graphml"), graph.to_graphml())?,
+    }
+
+    // Trail quality over the whole run
+    let mut csv = String::from("tick,food_x,food_y,optimal,trail,ratio\n");
+    for sample in &terrarium.optimality {
+        let trail = sample.trail.map_or(String::new(), |trail| trail.to_string());
+        let ratio = sample.ratio().map_or(String::new(), |ratio| ratio.to_string());
+        writeln!(csv, "{},{},{},{},{},{}", sample.tick, sample.food.0, sample.food.1, sample.optimal, trail, ratio).unwrap();
+    }
+    fs::write(PathBuf::from(EXPORT_DIR).join(format!("optimality_{:07}.csv", terrarium.tick)), csv)?;
+
+    // Branch traffic of double bridge next to the model
+    if let Some(bridge) = &terrarium.bridge {
+        fs::write(PathBuf::from(EXPORT_DIR).join(format!("bridge_{:07}.csv", terrarium.tick)), bridge.to_csv())?;
+    }
+    Ok(path)
+}
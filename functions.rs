@@ -1,5 +1,6 @@
-use ggez::{Context, GameResult};
 use crate::config::*;
+use rand::Rng;
+use std::collections::HashSet;
 
 // Translates location of two tunnels to location of pheromone linking these tunnel
 pub fn board_to_pheromones(x1: i32, y1: i32, x2: i32, y2: i32) -> Option<(i32, i32)> {
@@ -32,17 +33,183 @@ pub fn pheromones_to_board(x: i32, y: i32) -> Option<(i32, i32, i32, i32)> {
 }
 
 
-// Helper function for predefined rectangle
-pub fn predefined_rectangle_mesh(ctx: &mut Context, size: f32, color: ggez::graphics::Color) -> GameResult<ggez::graphics::Mesh> {
-    ggez::graphics::Mesh::new_rectangle(ctx, ggez::graphics::DrawMode::fill(), ggez::graphics::Rect::new(0.0, 0.0, size, size), color)
+// Scrambles bits of given value (splitmix64)
+pub fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// What random numbers are drawn for, streams of different purposes aren't correlated even for the same id
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Decide,
+    Hatch,
+    Collapse,
+    CollapseKill,
+    Rain,
+    ScenarioKill,
+}
+
+// Seed for random generator of one ant (or field) in one tick, doesn't depend on the order threads run in
+pub fn stream_seed(seed: u64, tick: u64, stream: Stream, id: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(stream as u64) ^ splitmix64(tick ^ splitmix64(id)))
+}
+
+// Checks if option like --tui was given on command line
+pub fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
+
+// Value given after option like --params tuned.cfg
+pub fn flag_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
 }
 
 
 // STRUCTURES & ENUMS
+
+// Parameters which can be changed while simulation runs, they start with values from config
+#[derive(Clone, Copy)]
+pub struct Params {
+    pub pheromones_intensity: f32,
+    pub evaporation_rate_fast: f32,
+    pub evaporation_rate_slow: f32,
+    pub desirability_pheromones: f32,
+    pub desirability_heuristics: f32,
+    pub digging_cost: f32,
+    pub ant_soil_limit: i32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            pheromones_intensity: PHEROMONES_INTENSITY,
+            evaporation_rate_fast: EVAPORATION_RATE_FAST,
+            evaporation_rate_slow: EVAPORATION_RATE_SLOW,
+            desirability_pheromones: DESIRABILITY_PHEROMONES,
+            desirability_heuristics: DESIRABILITY_HEURISTICS,
+            digging_cost: DIGGING_COST,
+            ant_soil_limit: ANT_SOIL_LIMIT,
+        }
+    }
+}
+
+impl Params {
+    pub const NAMES: &'static [&'static str] = &[
+        "pheromones_intensity",
+        "evaporation_rate_fast",
+        "evaporation_rate_slow",
+        "desirability_pheromones",
+        "desirability_heuristics",
+        "digging_cost",
+        "ant_soil_limit",
+    ];
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "pheromones_intensity" => Some(self.pheromones_intensity),
+            "evaporation_rate_fast" => Some(self.evaporation_rate_fast),
+            "evaporation_rate_slow" => Some(self.evaporation_rate_slow),
+            "desirability_pheromones" => Some(self.desirability_pheromones),
+            "desirability_heuristics" => Some(self.desirability_heuristics),
+            "digging_cost" => Some(self.digging_cost),
+            "ant_soil_limit" => Some(self.ant_soil_limit as f32),
+            _ => None,
+        }
+    }
+
+    // Changes parameter by name, refuses unknown names and values which would break the simulation
+    pub fn set(&mut self, name: &str, value: f32) -> Result<(), String> {
+        let valid = match name {
+            "evaporation_rate_fast" | "evaporation_rate_slow" => (0.0..=1.0).contains(&value),
+            "digging_cost" | "ant_soil_limit" => value >= 1.0,
+            _ if Params::NAMES.contains(&name) => value >= 0.0,
+            _ => return Err(format!("Unknown parameter {}", name)),
+        };
+        if !value.is_finite() || !valid {
+            return Err(format!("Invalid value {} for {}", value, name));
+        }
+
+        match name {
+            "pheromones_intensity" => self.pheromones_intensity = value,
+            "evaporation_rate_fast" => self.evaporation_rate_fast = value,
+            "evaporation_rate_slow" => self.evaporation_rate_slow = value,
+            "desirability_pheromones" => self.desirability_pheromones = value,
+            "desirability_heuristics" => self.desirability_heuristics = value,
+            "digging_cost" => self.digging_cost = value,
+            "ant_soil_limit" => self.ant_soil_limit = value.round() as i32,
+            _ => return Err(format!("Unknown parameter {}", name)),
+        }
+        Ok(())
+    }
+
+    // Config file with one "name = value" line for every parameter
+    pub fn to_config(&self) -> String {
+        Params::NAMES.iter().map(|&name| format!("{} = {}\n", name, self.get(name).unwrap())).collect()
+    }
+
+    // Reads config file, empty lines and lines starting with # are skipped, missing parameters keep values from config
+    // Errors name the line
+    pub fn parse(text: &str) -> Result<Params, String> {
+        let mut params = Params::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected name = value, got {}", index + 1, line));
+            };
+            let value: f32 = value.trim().parse().map_err(|_| format!("Line {}: value of {} is not a number", index + 1, name.trim()))?;
+            params.set(name.trim(), value).map_err(|error| format!("Line {}: {}", index + 1, error))?;
+        }
+        Ok(params)
+    }
+}
+
+// Heritable factors of one ant, its alpha, beta and soil limit are runtime parameters multiplied by them
+#[derive(Clone, Copy)]
+pub struct Traits {
+    pub pheromones: f32,
+    pub heuristics: f32,
+    pub soil_limit: f32,
+}
+
+impl Traits {
+    pub const NAMES: [&'static str; 3] = ["desirability_pheromones", "desirability_heuristics", "ant_soil_limit"];
+
+    // Founding ant, every factor is up to spread away from one
+    pub fn random(rng: &mut impl Rng, spread: f32) -> Traits {
+        let mut factor = || 1.0 + rng.gen_range(-1.0..=1.0) * spread;
+        Traits { pheromones: factor(), heuristics: factor(), soil_limit: factor() }
+    }
+
+    // Traits of larva, every factor moves a little from its parent's
+    pub fn mutated(&self, rng: &mut impl Rng) -> Traits {
+        let mut mutate = |value: f32| (value * (1.0 + rng.gen_range(-1.0..=1.0) * TRAIT_MUTATION)).max(0.01);
+        Traits { pheromones: mutate(self.pheromones), heuristics: mutate(self.heuristics), soil_limit: mutate(self.soil_limit) }
+    }
+
+    // Parameters as the ant with these traits has them
+    pub fn apply(&self, params: &Params) -> Params {
+        Params {
+            desirability_pheromones: params.desirability_pheromones * self.pheromones,
+            desirability_heuristics: params.desirability_heuristics * self.heuristics,
+            ant_soil_limit: ((params.ant_soil_limit as f32 * self.soil_limit).round() as i32).max(1),
+            ..*params
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct FoodSource {
     pub position: Position,
     pub amount: usize,
+    pub diurnal: bool, // Can be found only during day
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -62,7 +229,120 @@ impl Position{
     }
 }
 
+// Changes of the world ant wants to make, applied one ant after another in commit phase
+pub enum Intent {
+    Dig(Position),
+    Fill(Position),
+    TakeFood(usize),
+    EnterNest,
+}
+
+// Grid fields visited by ant, only visited fields are stored so memory grows with path and not with map
+#[derive(Default)]
+pub struct VisitedSet {
+    fields: HashSet<u32>,
+}
+
+impl VisitedSet {
+
+    // Index of field on grid
+    fn key(x: i32, y: i32) -> u32 {
+        x as u32 * HEIGHT as u32 + y as u32
+    }
+
+    pub fn insert(&mut self, x: i32, y: i32) {
+        self.fields.insert(VisitedSet::key(x, y));
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.fields.contains(&VisitedSet::key(x, y))
+    }
+
+    // Forgets everything but keeps allocated memory for next trip
+    pub fn clear(&mut self) {
+        self.fields.clear();
+    }
+}
+
+// Kind of soil, decides how big open spaces it can hold
+pub struct SoilType {
+    pub name: &'static str,
+    pub max_open: usize,       // Most open fields around tunnel (5x5 area) before it may collapse
+    pub max_overburden: u32,   // Most soil above tunnel before it may collapse
+    pub collapse_chance: f32,  // Chance of collapse of unstable tunnel per check
+    pub drain_rate: f32,       // Water soaking into soil per tick
+    pub color: (f32, f32, f32),
+}
+
+// Commands shared by keyboards of all front ends
+pub enum Control {
+    TogglePause,
+    Step, // One tick while paused
+    Faster,
+    Slower,
+}
+
 pub enum GameState {
     Playing,
     Paused,
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_changes_known_parameters() {
+        let mut params = Params::default();
+        params.set("digging_cost", 3.5).unwrap();
+        params.set("ant_soil_limit", 12.4).unwrap();
+        assert_eq!(params.get("digging_cost"), Some(3.5));
+        assert_eq!(params.get("ant_soil_limit"), Some(12.0));
+    }
+
+    #[test]
+    fn set_refuses_unknown_names_and_invalid_values() {
+        let mut params = Params::default();
+        assert_eq!(params.set("speed", 1.0), Err("Unknown parameter speed".to_string()));
+        assert_eq!(params.set("speed", -1.0), Err("Unknown parameter speed".to_string()));
+        assert!(params.set("evaporation_rate_fast", 1.5).is_err());
+        assert!(params.set("evaporation_rate_slow", -0.1).is_err());
+        assert!(params.set("digging_cost", 0.5).is_err());
+        assert!(params.set("ant_soil_limit", 0.0).is_err());
+        assert!(params.set("pheromones_intensity", -1.0).is_err());
+        assert!(params.set("desirability_pheromones", f32::NAN).is_err());
+        assert!(params.set("desirability_heuristics", f32::INFINITY).is_err());
+
+        // Refused values leave the parameter alone
+        assert_eq!(params.get("digging_cost"), Params::default().get("digging_cost"));
+    }
+
+    #[test]
+    fn parse_reads_config_written_by_to_config() {
+        let mut params = Params::default();
+        params.set("evaporation_rate_fast", 0.25).unwrap();
+        params.set("ant_soil_limit", 40.0).unwrap();
+
+        let parsed = Params::parse(&params.to_config()).unwrap();
+        for name in Params::NAMES {
+            assert_eq!(parsed.get(name), params.get(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_skips_comments_and_keeps_missing_parameters() {
+        let parsed = Params::parse("# tuned\n\n  digging_cost =  4  \n").unwrap();
+        assert_eq!(parsed.get("digging_cost"), Some(4.0));
+        assert_eq!(parsed.get("ant_soil_limit"), Params::default().get("ant_soil_limit"));
+    }
+
+    #[test]
+    fn parse_names_line_of_error() {
+        let error = |text: &str| Params::parse(text).err().unwrap();
+        assert_eq!(error("# comment\ndigging_cost 4"), "Line 2: expected name = value, got digging_cost 4");
+        assert_eq!(error("digging_cost = 2\n\nant_soil_limit = many"), "Line 3: value of ant_soil_limit is not a number");
+        assert_eq!(error("speed = 2"), "Line 1: Unknown parameter speed");
+        assert_eq!(error("digging_cost = 2\nevaporation_rate_slow = 2"), "Line 2: Invalid value 2 for evaporation_rate_slow");
+    }
+}
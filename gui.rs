@@ -0,0 +1,323 @@
+use ggez::event::{self, MouseButton};
+use ggez::graphics::*;
+use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::mint::Point2;
+use ggez::{Context, ContextBuilder, GameError, GameResult};
+use crate::config::*;
+use crate::functions::*;
+use crate::chambers::ChamberRole;
+use crate::clock::*;
+use crate::layers::GridLayer;
+#[cfg(feature = "record")]
+use crate::recorder::{self, Recorder};
+#[cfg(feature = "server")]
+use crate::server::Server;
+use crate::terrarium::Terrarium;
+
+// Window front end drawing terrarium with ggez
+pub struct Gui {
+    terrarium: Terrarium,
+
+    // Technical
+    soil_layer: GridLayer,
+    tunnel_layer: GridLayer,
+    pheromone_layer: GridLayer,
+    mound_layer: GridLayer,
+    chamber_layer: GridLayer,
+    water_layer: GridLayer,
+    pre_rendered_ant: Mesh,
+    pre_rendered_nest: Mesh,
+    pre_rendered_food: Mesh,
+    pre_rendered_sky: Mesh,
+    pre_rendered_night: Mesh,
+    ant_instances: InstanceArray,
+    #[cfg(feature = "record")]
+    recorder: Option<Recorder>,
+    #[cfg(feature = "server")]
+    server: Option<Server>,
+}
+
+// Helper function for predefined rectangle
+fn predefined_rectangle_mesh(ctx: &Context, size: f32, color: Color) -> GameResult<Mesh> {
+    Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0.0, 0.0, size, size), color)
+}
+
+// Saving of frames failed
+#[cfg(feature = "record")]
+fn record_error(error: image::ImageError) -> GameError {
+    GameError::CustomError(error.to_string())
+}
+
+// Build the context and event loop for the game (needed by game engine) and run it
+pub fn run(terrarium: Terrarium) -> GameResult {
+    let (ctx, event_loop) = ContextBuilder::new("ant_simulation", "Dominika")
+        .window_mode(ggez::conf::WindowMode::default().dimensions(WINDOW_WIDTH, WINDOW_HEIGHT))
+        .build()?;
+    let state = Gui::new(&ctx, terrarium)?;
+    event::run(ctx, event_loop, state)
+}
+
+impl Gui {
+
+    // Prepares everything needed for drawing
+    pub fn new(ctx: &Context, terrarium: Terrarium) -> GameResult<Gui> {
+
+        // Soil never changes its type, so its layer is filled once
+        let mut soil_layer = GridLayer::new(ctx, WIDTH as u32, HEIGHT as u32);
+        for (x, column) in terrarium.soil.iter().enumerate() {
+            for (y, &soil_type) in column.iter().enumerate() {
+                let (r, g, b) = SOIL_TYPES[soil_type as usize].color;
+                soil_layer.set(x as u32, y as u32, Color::new(r, g, b, 1.0));
+            }
+        }
+
+        // Shared meshes are white, every instance gets its own colour
+        let pre_rendered_ant = Mesh::new_circle(ctx, DrawMode::fill(), Point2 { x: 0.0, y: 0.0 }, ANT_SCALE, 0.1, Color::WHITE).unwrap();
+        let pre_rendered_nest = predefined_rectangle_mesh(ctx, NEST_SIZE, Color::new(0.141, 0.090, 0.078, 1.0)).unwrap();
+        let pre_rendered_food = predefined_rectangle_mesh(ctx, FOOD_SCALE, Color::GREEN).unwrap();
+        let pre_rendered_night = predefined_rectangle_mesh(ctx, WINDOW_WIDTH.max(WINDOW_HEIGHT), Color::WHITE).unwrap();
+        let pre_rendered_sky = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0.0, 0.0, WINDOW_WIDTH, SURFACE_ROW as f32 * WINDOW_TO_GAME_SCALE), Color::new(0.53, 0.75, 0.92, 1.0)).unwrap();
+
+        let mut gui = Gui {
+            terrarium,
+            soil_layer,
+            tunnel_layer: GridLayer::new(ctx, WIDTH as u32, HEIGHT as u32),
+            // Pheromone lies halfway between two tunnels, so its layer has twice the resolution
+            pheromone_layer: GridLayer::new(ctx, 2 * WIDTH as u32, 2 * HEIGHT as u32),
+            mound_layer: GridLayer::new(ctx, WIDTH as u32, HEIGHT as u32),
+            chamber_layer: GridLayer::new(ctx, WIDTH as u32, HEIGHT as u32),
+            water_layer: GridLayer::new(ctx, WIDTH as u32, HEIGHT as u32),
+            pre_rendered_ant,
+            pre_rendered_nest,
+            pre_rendered_food,
+            pre_rendered_sky,
+            pre_rendered_night,
+            ant_instances: InstanceArray::new(ctx, None),
+            #[cfg(feature = "record")]
+            recorder: if has_flag("--record") { Some(Recorder::start().map_err(record_error)?) } else { None },
+            #[cfg(feature = "server")]
+            server: if has_flag("--server") { Some(Server::start()?) } else { None },
+        };
+
+        // Later frames redraw only fields which changed
+        gui.terrarium.take_changed();
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                gui.redraw_field(x, y);
+            }
+        }
+        Ok(gui)
+    }
+
+    // Draws tunnel or obstacle, mound and water of one field into their layers
+    fn redraw_field(&mut self, x: usize, y: usize) {
+        let terrarium = &self.terrarium;
+
+        let color = if terrarium.obstacles[x][y] {
+            Color::new(0.5, 0.5, 0.5, 1.0)
+        } else if terrarium.tunnels[x][y] {
+            Color::new(0.4, 0.2, 0.1, 1.0)
+        } else {
+            Color::new(0.0, 0.0, 0.0, 0.0)
+        };
+        self.tunnel_layer.set(x as u32, y as u32, color);
+
+        // Mound in side view is a column of soil on the surface, its height is kept in the first field, otherwise higher soil is more visible
+        if SIDE_VIEW {
+            if y == 0 {
+                for sky in 0..SURFACE_ROW {
+                    let color = if terrarium.mound.covers(x, sky) { Color::new(0.561, 0.361, 0.231, 1.0) } else { Color::new(0.0, 0.0, 0.0, 0.0) };
+                    self.mound_layer.set(x as u32, sky as u32, color);
+                }
+            }
+        } else {
+            let height = terrarium.mound.heights[x][y] as f32;
+            let color = if height > 0.0 { Color::new(0.561, 0.361, 0.231, (0.3 + 0.1 * height).min(1.0)) } else { Color::new(0.0, 0.0, 0.0, 0.0) };
+            self.mound_layer.set(x as u32, y as u32, color);
+        }
+
+        // Deeper water is darker
+        let water = terrarium.weather.water[x][y];
+        let color = if water > 0.0 { Color::new(0.2, 0.4, 0.9, 0.3 + 0.6 * water) } else { Color::new(0.0, 0.0, 0.0, 0.0) };
+        self.water_layer.set(x as u32, y as u32, color);
+    }
+}
+
+// Update and Draw functions called from game engine
+impl event::EventHandler for Gui {
+
+    // Updates every element of game
+    fn update(&mut self, _: &mut Context) -> GameResult {
+        #[cfg(feature = "server")]
+        if let Some(server) = &mut self.server {
+            server.poll(&mut self.terrarium);
+        }
+
+        self.terrarium.update();
+
+        #[cfg(feature = "server")]
+        if let Some(server) = &mut self.server {
+            server.broadcast(&self.terrarium);
+        }
+        Ok(())
+    }
+
+    // After update, this function is called to draw everything
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+
+        // Tunnels, obstacles, mound and water are redrawn only where they changed since last frame
+        for (x, y) in self.terrarium.take_changed() {
+            self.redraw_field(x, y);
+        }
+        let terrarium = &self.terrarium;
+
+        // Create Canvas to draw on
+        let mut canvas = Canvas::from_frame(ctx, Color::new(0.22, 0.15, 0.13, 1.0));
+
+        // Render soil
+        self.soil_layer.draw(ctx, &mut canvas, WINDOW_TO_GAME_SCALE);
+
+        // Render sky
+        if SIDE_VIEW {
+            canvas.draw(&self.pre_rendered_sky, DrawParam::default());
+        }
+
+        // Render tunnels, obstacles and mound
+        self.tunnel_layer.draw(ctx, &mut canvas, WINDOW_TO_GAME_SCALE);
+        self.mound_layer.draw(ctx, &mut canvas, WINDOW_TO_GAME_SCALE);
+
+        // Render dug parts of chambers in colour of their role
+        for chamber in &terrarium.colony.chambers {
+            let color = match chamber.role {
+                ChamberRole::Storage => Color::new(0.2, 0.6, 0.2, 0.5),
+                ChamberRole::Brood => Color::new(0.9, 0.85, 0.6, 0.5),
+                ChamberRole::Midden => Color::new(0.35, 0.3, 0.25, 0.5),
+            };
+            for &(x, y) in &chamber.fields[..chamber.dug] {
+                self.chamber_layer.set(x as u32, y as u32, color);
+            }
+        }
+        self.chamber_layer.draw(ctx, &mut canvas, WINDOW_TO_GAME_SCALE);
+        self.water_layer.draw(ctx, &mut canvas, WINDOW_TO_GAME_SCALE);
+
+        // Render pheromones above visibility limit, layer skips cells that didn't change
+        for (x, row) in terrarium.pheromones.iter().enumerate() {
+            for (y, pheromone) in row.iter().enumerate() {
+                if let Some((x1, y1, x2, y2)) = pheromones_to_board(x as i32, y as i32) {
+                    let alpha = if *pheromone > MIN_PHEROMONES { *pheromone/MAX_PHEROMONES } else { 0.0 };
+                    self.pheromone_layer.set((x1 + x2) as u32, (y1 + y2) as u32, Color::new(1.0, 1.0, 1.0, alpha));
+                }
+            }
+        }
+        self.pheromone_layer.draw(ctx, &mut canvas, WINDOW_TO_GAME_SCALE / 2.0);
+
+        // Render ants all at once
+        self.ant_instances.set(terrarium.ants.iter().map(|ant| {
+            let (r, g, b) = ant.color();
+            DrawParam::default().dest([ant.position.x as f32, ant.position.y as f32]).color(Color::new(r, g, b, 1.0))
+        }));
+        canvas.draw_instanced_mesh(self.pre_rendered_ant.clone(), &self.ant_instances, DrawParam::default());
+
+        // Render nest
+        let nest_pos = [terrarium.nest.x-(NEST_SIZE/2.0) as i32  , terrarium.nest.y-(NEST_SIZE/2.0) as i32];
+        canvas.draw(&self.pre_rendered_nest, DrawParam::default().dest([nest_pos[0] as f32, nest_pos[1] as f32]));
+        // Render text on nest
+        let text = Text::new(TextFragment {
+            text: format!("{}", terrarium.colony.total(ChamberRole::Storage)),
+            color: Some(Color::WHITE),
+            scale: Some(PxScale::from(15.0)),
+            ..Default::default()
+        });
+        let dimensions = text.dimensions(ctx);
+        let text_pos = Point2 {
+            x: (nest_pos[0] as f32 + NEST_SIZE / 2.0 - dimensions.unwrap().w / 2.0).round(),
+            y: (nest_pos[1] as f32 + NEST_SIZE / 2.0 - dimensions.unwrap().h/2.0).round(),
+        };
+        canvas.draw(&text, DrawParam::default().dest(text_pos));
+
+        // Render food sources
+        for food in &terrarium.food_sources {
+            if food.amount > 0 {
+                let food_size = FOOD_SCALE;
+                let pos = [food.position.x-(FOOD_SCALE/2.0) as i32, food.position.y-(FOOD_SCALE/2.0) as i32];
+                // Diurnal food is faded during night
+                let alpha = if food.diurnal && is_night(terrarium.tick) { 0.3 } else { 1.0 };
+                canvas.draw(&self.pre_rendered_food, DrawParam::default().dest([pos[0] as f32, pos[1] as f32]).color(Color::new(1.0, 1.0, 1.0, alpha)), );
+
+                // Render text on food
+                let text = Text::new(TextFragment {
+                    text: format!("{}", food.amount),
+                    color: Some(Color::BLACK),
+                    scale: Some(PxScale::from(25.0)),
+                    ..Default::default()
+                });
+                let dimensions = text.dimensions(ctx);
+                let text_pos = Point2 {
+                    x: (pos[0] as f32 + food_size / 2.0 - dimensions.unwrap().w / 2.0).round(),
+                    y: (pos[1] as f32 + food_size / 2.0 - dimensions.unwrap().h/2.0).round(),
+                };
+                canvas.draw(&text, DrawParam::default().dest(text_pos));
+            }
+        }
+
+        // Darken scene during night
+        let darkness = (-daylight(terrarium.tick)).max(0.0) * 0.5;
+        if darkness > 0.0 {
+            canvas.draw(&self.pre_rendered_night, DrawParam::default().color(Color::new(0.05, 0.05, 0.2, darkness)));
+        }
+
+        // Render HUD
+        let mut hud_lines = vec![format!("{:.1} ms ({:.0} FPS)", ctx.time.delta().as_secs_f64() * 1000.0, ctx.time.fps())];
+        hud_lines.extend(terrarium.status_lines());
+
+        // Recorded frames are rasterised from the terrarium, not read back from the window
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &mut self.recorder {
+            recorder.capture(terrarium).map_err(record_error)?;
+            hud_lines.push(recorder.status());
+        }
+        let frame_text = Text::new(TextFragment {
+            text: hud_lines.join("\n"),
+            color: Some(Color::WHITE),
+            scale: Some(PxScale::from(15.0)),
+            ..Default::default()
+        });
+        canvas.draw(&frame_text, DrawParam::default().dest([5.0, 5.0]));
+
+        //Finish drawing
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+
+    // Adding food by click
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> Result<(), GameError> {
+        if button == MouseButton::Left {
+            self.terrarium.spawn_food(Position { x: x as i32, y: y as i32 });
+        }
+        Ok(())
+    }
+
+    // Play/Pause, step and speed handler - function from ChatGPT
+    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeated: bool) -> Result<(), GameError> {
+        let control = match input.keycode {
+            Some(KeyCode::Space) => Some(Control::TogglePause),
+            Some(KeyCode::S) => Some(Control::Step),
+            Some(KeyCode::Equals) | Some(KeyCode::Plus) | Some(KeyCode::NumpadAdd) => Some(Control::Faster),
+            Some(KeyCode::Minus) | Some(KeyCode::NumpadSubtract) => Some(Control::Slower),
+            Some(KeyCode::E) => {
+                self.terrarium.export()?;
+                None
+            }
+            #[cfg(feature = "record")]
+            Some(KeyCode::R) => {
+                recorder::toggle(&mut self.recorder).map_err(record_error)?;
+                None
+            }
+            _ => None,
+        };
+        if let Some(control) = control {
+            self.terrarium.control(control);
+        }
+        Ok(())
+    }
+}
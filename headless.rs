@@ -0,0 +1,57 @@
+use std::error::Error;
+use crate::functions::has_flag;
+#[cfg(feature = "record")]
+use crate::recorder::Recorder;
+#[cfg(feature = "server")]
+use crate::server::Server;
+use crate::stop::StopConditions;
+use crate::terrarium::Terrarium;
+
+// Runs simulation without any window or terminal drawing until some stop condition is met, with --record saving every RECORD_EVERYth tick as a frame
+pub fn run(mut terrarium: Terrarium) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "record")]
+    let mut recorder = if has_flag("--record") { Some(Recorder::start()?) } else { None };
+
+    #[cfg(feature = "server")]
+    let mut server = if has_flag("--server") { Some(Server::start()?) } else { None };
+
+    let mut stop = StopConditions::new();
+    let reason = loop {
+        #[cfg(feature = "server")]
+        if let Some(server) = &mut server {
+            server.poll(&mut terrarium);
+            server.broadcast(&terrarium);
+
+            // Only clients can pause headless run
+            if terrarium.is_paused() {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                continue;
+            }
+        }
+
+        terrarium.step();
+
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &mut recorder {
+            recorder.capture(&terrarium)?;
+        }
+
+        if let Some(reason) = stop.check(&mut terrarium) {
+            break reason;
+        }
+    };
+
+    if has_flag("--export") {
+        terrarium.export()?;
+    }
+
+    for line in terrarium.status_lines() {
+        println!("{}", line);
+    }
+    #[cfg(feature = "record")]
+    if let Some(recorder) = &recorder {
+        println!("{}", recorder.status());
+    }
+    println!("Stopped at tick {}: {}", terrarium.tick, reason);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use ggez::Context;
+use ggez::graphics::{Canvas, Color, DrawParam, Image, ImageFormat, Sampler};
+
+// Grid drawn as one texture, only pixels changed since last frame are sent to GPU
+pub struct GridLayer {
+    image: Image,
+    pixels: Vec<u8>, // RGBA, row after row
+    width: u32,
+    height: u32,
+    dirty: Option<(u32, u32, u32, u32)>, // Smallest rectangle (x_min, y_min, x_max, y_max) holding all changes
+}
+
+impl GridLayer {
+
+    // Creates fully transparent layer
+    pub fn new(ctx: &Context, width: u32, height: u32) -> Self {
+        let pixels = vec![0; (width * height * 4) as usize];
+        let image = Image::from_pixels(ctx, &pixels, ImageFormat::Rgba8UnormSrgb, width, height);
+
+        GridLayer { image, pixels, width, height, dirty: None }
+    }
+
+    // Changes colour of one cell, remembers it only if it really changed
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let (r, g, b, a) = color.to_rgba();
+        let index = ((y * self.width + x) * 4) as usize;
+        if self.pixels[index..index + 4] == [r, g, b, a] {
+            return;
+        }
+        self.pixels[index..index + 4].copy_from_slice(&[r, g, b, a]);
+
+        self.dirty = Some(match self.dirty {
+            Some((x_min, y_min, x_max, y_max)) => (x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+
+    // Sends changed part of the grid to the texture
+    fn upload(&mut self, ctx: &Context) {
+        if let Some((x_min, y_min, x_max, y_max)) = self.dirty.take() {
+            ctx.gfx.wgpu().queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: self.image.wgpu().0,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: x_min, y: y_min, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &self.pixels,
+                wgpu::ImageDataLayout {
+                    offset: ((y_min * self.width + x_min) * 4) as u64,
+                    bytes_per_row: Some(self.width * 4),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d { width: x_max - x_min + 1, height: y_max - y_min + 1, depth_or_array_layers: 1 },
+            );
+        }
+    }
+
+    // Draws whole layer scaled up without blurring cells
+    pub fn draw(&mut self, ctx: &Context, canvas: &mut Canvas, scale: f32) {
+        self.upload(ctx);
+        canvas.set_sampler(Sampler::nearest_clamp());
+        canvas.draw(&self.image, DrawParam::default().scale([scale, scale]));
+        canvas.set_default_sampler();
+    }
+}
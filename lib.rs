@@ -0,0 +1,38 @@
+// Simulation core and front ends, shared by the binary and the Python module
+// Constructors of simulation parts take no arguments but depend on config, so there is no Default
+#![allow(clippy::new_without_default)]
+
+pub mod terrarium;
+pub mod config;
+pub mod ants;
+pub mod functions;
+pub mod behaviour;
+pub mod mound;
+pub mod events;
+pub mod stability;
+pub mod chambers;
+pub mod weather;
+pub mod clock;
+pub mod export;
+pub mod optimality;
+pub mod stop;
+pub mod environment;
+pub mod tuning;
+pub mod neural;
+pub mod scenario;
+pub mod presets;
+#[cfg(feature = "gui")]
+pub mod layers;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod raster;
+pub mod headless;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "record")]
+pub mod recorder;
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "python")]
+mod python;
@@ -1,23 +1,62 @@
-mod terrarium;
-mod config;
-mod ants;
-mod functions;
+use ants::terrarium::*;
+use ants::config::*;
+use ants::functions::*;
+use ants::headless;
+use ants::tuning;
+use ants::neural;
+use ants::scenario::Scenario;
+use ants::presets;
+#[cfg(feature = "gui")]
+use ants::gui;
+#[cfg(feature = "tui")]
+use ants::tui;
+use std::error::Error;
+use std::fs;
 
-use terrarium::*;
-use config::*;
-use ggez::event::{self};
-use ggez::{Context, ContextBuilder, GameResult};
+// Main function, --tui runs simulation in terminal instead of window, --headless without any drawing
+// --tune searches for best parameters, --params file.cfg starts with parameters from file, --evolve trains neural ants
+// --scenario file.txt runs timed events from file, --preset name starts one of built-in experiments (without --params)
+fn main() -> Result<(), Box<dyn Error>> {
+    if THREAD_COUNT != 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(THREAD_COUNT).build_global().expect("Thread pool already built");
+    }
+    if has_flag("--tune") {
+        return tuning::run();
+    }
+    if has_flag("--evolve") {
+        return neural::evolve();
+    }
 
-// Build the context and event loop for the game (needed by game engine)
-fn build_context() -> GameResult<(Context, event::EventLoop<()>)> {
-    ContextBuilder::new("ant_simulation", "Dominika")
-        .window_mode(ggez::conf::WindowMode::default().dimensions(WINDOW_WIDTH, WINDOW_HEIGHT))
-        .build()
-}
+    // Experiments compare runs with parameters from config, file would change them behind their back
+    if has_flag("--preset") && has_flag("--params") {
+        return Err("--preset can't be combined with --params, experiments run with parameters from config".into());
+    }
+    let mut terrarium = match flag_value("--preset") {
+        Some(name) => presets::build(&name, SEED)?.ok_or_else(|| format!("Unknown preset {}, expected one of {:?}", name, presets::PRESETS))?,
+        None => Terrarium::new()?,  // Initialize the game state with a fresh environment
+    };
+    if let Some(path) = flag_value("--params") {
+        terrarium.params = Params::parse(&fs::read_to_string(path)?)?;
+    }
+    if let Some(path) = flag_value("--scenario") {
+        if terrarium.scenario.pending() > 0 {
+            return Err("This preset runs its own scenario, --scenario can't replace it".into());
+        }
+        terrarium.scenario = Scenario::load(path)?;
+    }
+
+    if has_flag("--headless") {
+        return headless::run(terrarium);
+    }
+
+    #[cfg(feature = "tui")]
+    if has_flag("--tui") || cfg!(not(feature = "gui")) {
+        return Ok(tui::run(terrarium)?);
+    }
+
+    #[cfg(feature = "gui")]
+    return Ok(gui::run(terrarium)?);  // Run the game loop
 
-// Main function
-fn main() -> GameResult {
-    let (ctx, event_loop) = build_context()?;  // Build context and event loop
-    let state = Terrarium::new(&ctx);  // Initialize the game state with a fresh environment
-    event::run(ctx, event_loop, state)  // Run the game loop
+    #[cfg(not(feature = "gui"))]
+    headless::run(terrarium)
 }
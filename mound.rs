@@ -0,0 +1,67 @@
+use crate::config::*;
+
+// Heap of excavated soil, height of soil on every field of the grid
+// In side view only first value of every column is used - height of soil lying on the surface
+pub struct Mound {
+    pub heights: Vec<Vec<u32>>,
+    pub volume: u64,
+}
+
+impl Mound {
+
+    pub fn new() -> Self {
+        Mound { heights: vec![vec![0; HEIGHT]; WIDTH], volume: 0 }
+    }
+
+    // Drops one grain of soil, it rolls down while the slope is steeper than soil can hold, returns field where it stopped
+    pub fn deposit(&mut self, x: usize, y: usize) -> (usize, usize) {
+        let (mut x, mut y) = (x.min(WIDTH - 1), if SIDE_VIEW { 0 } else { y.min(HEIGHT - 1) });
+        self.heights[x][y] += 1;
+        self.volume += 1;
+
+        while let Some((nx, ny)) = self.lowest_neighbour(x, y) {
+            if self.heights[x][y] <= self.heights[nx][ny] + MOUND_REPOSE {
+                break;
+            }
+            self.heights[x][y] -= 1;
+            self.heights[nx][ny] += 1;
+            (x, y) = (nx, ny);
+        }
+        (x, y)
+    }
+
+    // Takes one grain off the field, false if there is none
+    pub fn remove(&mut self, x: usize, y: usize) -> bool {
+        if self.heights[x][y] == 0 {
+            return false;
+        }
+        self.heights[x][y] -= 1;
+        self.volume -= 1;
+        true
+    }
+
+    // Neighbour with least soil, first one wins when equal
+    fn lowest_neighbour(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let potential = [
+            (x as i32 - 1, y as i32),
+            (x as i32 + 1, y as i32),
+            (x as i32, y as i32 - 1),
+            (x as i32, y as i32 + 1),
+        ];
+
+        // In side view soil can roll only left or right
+        let count = if SIDE_VIEW { 2 } else { 4 };
+
+        potential[..count]
+            .iter()
+            .copied()
+            .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && (nx as usize) < WIDTH && (ny as usize) < HEIGHT)
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+            .min_by_key(|&(nx, ny)| self.heights[nx][ny])
+    }
+
+    // Side view only, checks if loose soil lies on given field of the sky
+    pub fn covers(&self, x: usize, y: usize) -> bool {
+        SIDE_VIEW && y < SURFACE_ROW && SURFACE_ROW - y <= self.heights[x][0] as usize
+    }
+}
@@ -0,0 +1,335 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use crate::config::*;
+use crate::behaviour::*;
+use crate::functions::*;
+use crate::terrarium::Terrarium;
+use crate::tuning;
+
+// Pheromone and move flag for every direction, carried food and soil, nest direction, food direction
+const INPUTS: usize = 4 + 4 + 2 + 2 + 2;
+const OUTPUTS: usize = 4; // Preference of every direction, in order of Move::ALL
+
+// Small feed-forward network, tanh in hidden layers
+#[derive(Clone)]
+pub struct Network {
+    sizes: Vec<usize>,
+    weights: Vec<Vec<f32>>, // For every layer outputs * (inputs + 1), last of every row is bias
+}
+
+impl Network {
+
+    // Network with inputs and outputs of ant and hidden layers from config, small random weights
+    pub fn random(rng: &mut impl Rng) -> Network {
+        let mut sizes = vec![INPUTS];
+        sizes.extend_from_slice(NEURAL_HIDDEN);
+        sizes.push(OUTPUTS);
+
+        let weights = sizes
+            .windows(2)
+            .map(|pair| {
+                let range = 1.0 / (pair[0] as f32).sqrt();
+                (0..pair[1] * (pair[0] + 1)).map(|_| rng.gen_range(-range..=range)).collect()
+            })
+            .collect();
+        Network { sizes, weights }
+    }
+
+    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
+        let mut values = input.to_vec();
+        for (layer, weights) in self.weights.iter().enumerate() {
+            let last = layer + 1 == self.weights.len();
+            values = weights
+                .chunks(values.len() + 1)
+                .map(|row| {
+                    let sum = row[values.len()] + row.iter().zip(&values).map(|(weight, value)| weight * value).sum::<f32>();
+                    if last { sum } else { sum.tanh() }
+                })
+                .collect();
+        }
+        values
+    }
+
+    // Copy with every weight moved by up to NEURAL_MUTATION_SIZE
+    pub fn mutated(&self, rng: &mut impl Rng) -> Network {
+        let weights = self.weights
+            .iter()
+            .map(|layer| layer.iter().map(|weight| weight + rng.gen_range(-1.0..=1.0) * NEURAL_MUTATION_SIZE).collect())
+            .collect();
+        Network { sizes: self.sizes.clone(), weights }
+    }
+
+    // Text file - first line sizes of layers, then weights of every layer on its own line
+    fn to_text(&self) -> String {
+        let mut text = self.sizes.iter().map(usize::to_string).collect::<Vec<_>>().join(" ");
+        for layer in &self.weights {
+            text.push('\n');
+            text.push_str(&layer.iter().map(f32::to_string).collect::<Vec<_>>().join(" "));
+        }
+        text.push('\n');
+        text
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    // Reads text written by save, empty lines are skipped, errors name the line
+    pub fn parse(text: &str) -> io::Result<Network> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line)).filter(|(_, line)| !line.trim().is_empty());
+
+        let (line, first) = lines.next().ok_or_else(|| invalid("Empty network file".to_string()))?;
+        let sizes: Vec<usize> = first
+            .split_whitespace()
+            .map(|size| size.parse().map_err(|_| invalid(format!("Line {}: layer size {} is not a number", line, size))))
+            .collect::<io::Result<_>>()?;
+        if sizes.len() < 2 || sizes[0] != INPUTS || sizes[sizes.len() - 1] != OUTPUTS {
+            return Err(invalid(format!("Line {}: network has to take {} inputs and give {} outputs", line, INPUTS, OUTPUTS)));
+        }
+        if sizes.contains(&0) {
+            return Err(invalid(format!("Line {}: layer with 0 neurons", line)));
+        }
+
+        let mut weights = Vec::new();
+        for pair in sizes.windows(2) {
+            let (line, text) = lines.next().ok_or_else(|| invalid(format!("Missing layer of weights, file has {} layers", weights.len())))?;
+            let layer: Vec<f32> = text
+                .split_whitespace()
+                .map(|weight| weight.parse().map_err(|_| invalid(format!("Line {}: weight {} is not a number", line, weight))))
+                .collect::<io::Result<_>>()?;
+            if layer.len() != pair[1] * (pair[0] + 1) {
+                return Err(invalid(format!("Line {}: layer needs {} weights, got {}", line, pair[1] * (pair[0] + 1), layer.len())));
+            }
+            weights.push(layer);
+        }
+        Ok(Network { sizes, weights })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Network> {
+        Network::parse(&fs::read_to_string(path)?)
+    }
+}
+
+// Unit vector from ant to target in grid, zeros if there is none
+fn direction(from: Position, to: Option<Position>) -> [f32; 2] {
+    let Some(to) = to else {
+        return [0.0; 2];
+    };
+    let (dx, dy) = ((to.get_x_grid() - from.get_x_grid()) as f32, (to.get_y_grid() - from.get_y_grid()) as f32);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 { [0.0; 2] } else { [dx / distance, dy / distance] }
+}
+
+// Index of direction from ant to its neighbour
+fn direction_of(surroundings: &Surroundings, neighbour: &Neighbour) -> Option<usize> {
+    let offset = (
+        neighbour.position.get_x_grid() - surroundings.position.get_x_grid(),
+        neighbour.position.get_y_grid() - surroundings.position.get_y_grid(),
+    );
+    Move::ALL[..OUTPUTS].iter().position(|chosen| chosen.offset() == offset)
+}
+
+// What network sees
+fn inputs(surroundings: &Surroundings) -> Vec<f32> {
+    let mut pheromones = [0.0; 4];
+    let mut moves = [0.0; 4];
+    for neighbour in &surroundings.neighbours {
+        if let Some(index) = direction_of(surroundings, neighbour) {
+            pheromones[index] = (neighbour.pheromone / MAX_PHEROMONES).min(1.0);
+            moves[index] = if neighbour.tunnel { 1.0 } else { 0.5 };
+        }
+    }
+
+    let mut inputs = Vec::with_capacity(INPUTS);
+    inputs.extend(pheromones);
+    inputs.extend(moves);
+    inputs.push(surroundings.carrying_food as u8 as f32);
+    inputs.push(surroundings.soil_carried as f32 / surroundings.params.ant_soil_limit as f32);
+    inputs.extend(direction(surroundings.position, Some(surroundings.nest)));
+    inputs.extend(direction(surroundings.position, surroundings.food));
+    inputs
+}
+
+// Ant driven by neural network instead of the probability formula
+pub struct NeuralBehavior {
+    pub network: Network,
+}
+
+impl NeuralBehavior {
+
+    // Weights saved earlier, random network seeded by SEED if there are none, error names the file
+    pub fn from_file_or_random(path: &str) -> io::Result<NeuralBehavior> {
+        let network = if Path::new(path).exists() {
+            Network::load(path).map_err(|error| io::Error::new(error.kind(), format!("Can't load neural network from {}: {}", path, error)))?
+        } else {
+            Network::random(&mut StdRng::seed_from_u64(SEED))
+        };
+        Ok(NeuralBehavior { network })
+    }
+}
+
+impl AntBehavior for NeuralBehavior {
+    fn decide(&self, surroundings: &Surroundings, rng: &mut StdRng) -> AntAction {
+        if surroundings.returning && !surroundings.carrying_food {
+            return AntAction::Backtrack;
+        }
+
+        let neighbours = candidates(surroundings);
+        if neighbours.is_empty() {
+            return AntAction::Backtrack;
+        }
+
+        // Preferences of possible moves turned into probabilities (softmax)
+        let preferences = self.network.forward(&inputs(surroundings));
+        let scores: Vec<f32> = neighbours
+            .iter()
+            .map(|neighbour| direction_of(surroundings, neighbour).map_or(f32::NEG_INFINITY, |index| preferences[index]))
+            .collect();
+        let best = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<f32> = scores.iter().map(|score| ((score - best) / NEURAL_TEMPERATURE).exp()).collect();
+
+        let mut choice = rng.gen::<f32>() * weights.iter().sum::<f32>();
+        for (neighbour, weight) in neighbours.iter().zip(&weights) {
+            if choice <= *weight {
+                return step_into(neighbour);
+            }
+            choice -= weight;
+        }
+        AntAction::Wait
+    }
+}
+
+// Average fitness of network over seeded headless runs, all castes use it
+fn evaluate(network: &Network) -> io::Result<f32> {
+    let scores: Vec<f32> = (0..TUNE_SEEDS)
+        .into_par_iter()
+        .map(|run| {
+            let mut terrarium = Terrarium::with_seed(SEED + run)?;
+            for caste in 0..CASTES.len() {
+                terrarium.set_behaviour(caste, Box::new(NeuralBehavior { network: network.clone() }));
+            }
+            Ok(tuning::score(terrarium))
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(scores.iter().sum::<f32>() / TUNE_SEEDS as f32)
+}
+
+// Neuroevolution - best networks survive, the rest are their mutated copies, best one is saved to NEURAL_WEIGHTS
+pub fn evolve() -> Result<(), Box<dyn Error>> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    fs::create_dir_all(TUNE_DIR)?;
+
+    // Saved network is improved further, otherwise evolution starts from random ones
+    let mut population: Vec<Network> = Vec::new();
+    if Path::new(NEURAL_WEIGHTS).exists() {
+        population.push(Network::load(NEURAL_WEIGHTS)?);
+    }
+    while population.len() < NEURAL_POPULATION.max(2) {
+        population.push(Network::random(&mut rng));
+    }
+
+    let mut history = String::from("generation,best,mean,worst\n");
+    let mut best: Option<(f32, Network)> = None;
+    for generation in 0..NEURAL_GENERATIONS {
+        let mut scored: Vec<(f32, Network)> = population
+            .into_par_iter()
+            .map(|network| Ok((evaluate(&network)?, network)))
+            .collect::<io::Result<_>>()?;
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mean = scored.iter().map(|(fitness, _)| fitness).sum::<f32>() / scored.len() as f32;
+        writeln!(history, "{},{},{},{}", generation, scored[0].0, mean, scored[scored.len() - 1].0)?;
+        println!("Generation {}: best {:.5}, mean {:.5}", generation, scored[0].0, mean);
+
+        if best.as_ref().is_none_or(|(fitness, _)| scored[0].0 > *fitness) {
+            best = Some(scored[0].clone());
+        }
+
+        let parents: Vec<Network> = scored.into_iter().take(NEURAL_ELITE.max(1)).map(|(_, network)| network).collect();
+        population = parents.clone();
+        while population.len() < NEURAL_POPULATION.max(2) {
+            let parent = &parents[rng.gen_range(0..parents.len())];
+            population.push(parent.mutated(&mut rng));
+        }
+    }
+
+    fs::write(PathBuf::from(TUNE_DIR).join("neural_history.csv"), history)?;
+    if let Some((fitness, network)) = best {
+        network.save(NEURAL_WEIGHTS)?;
+        println!("Best {} {:.5}, network saved to {}", TUNE_FITNESS, fitness, NEURAL_WEIGHTS);
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        Network::parse(text).err().unwrap().to_string()
+    }
+
+    // Sizes line and one line of zero weights for every layer
+    fn text_of(sizes: &[usize]) -> String {
+        let mut text = sizes.iter().map(usize::to_string).collect::<Vec<_>>().join(" ");
+        for pair in sizes.windows(2) {
+            text.push('\n');
+            text.push_str(&vec!["0"; pair[1] * (pair[0] + 1)].join(" "));
+        }
+        text
+    }
+
+    #[test]
+    fn saved_network_loads_back_the_same() {
+        let network = Network::random(&mut StdRng::seed_from_u64(1));
+        let path = std::env::temp_dir().join(format!("ants_network_{}.txt", std::process::id()));
+        network.save(&path).unwrap();
+        let loaded = Network::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.sizes, network.sizes);
+        assert_eq!(loaded.weights, network.weights);
+        let input: Vec<f32> = (0..INPUTS).map(|index| index as f32 / INPUTS as f32).collect();
+        assert_eq!(loaded.forward(&input), network.forward(&input));
+    }
+
+    #[test]
+    fn parse_skips_empty_lines() {
+        let text = text_of(&[INPUTS, 3, OUTPUTS]).replace('\n', "\n\n");
+        let network = Network::parse(&format!("\n{}\n\n", text)).unwrap();
+        assert_eq!(network.sizes, vec![INPUTS, 3, OUTPUTS]);
+        assert_eq!(network.forward(&[0.0; INPUTS]), vec![0.0; OUTPUTS]);
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        let path = std::env::temp_dir().join("ants_network_which_does_not_exist.txt");
+        assert_eq!(Network::load(path).err().unwrap().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn parse_refuses_malformed_files() {
+        assert_eq!(error(""), "Empty network file");
+        assert_eq!(error("\n\n14 x 4"), "Line 3: layer size x is not a number");
+        assert_eq!(error(&INPUTS.to_string()), format!("Line 1: network has to take {} inputs and give {} outputs", INPUTS, OUTPUTS));
+        assert_eq!(error(&text_of(&[INPUTS + 1, OUTPUTS])), format!("Line 1: network has to take {} inputs and give {} outputs", INPUTS, OUTPUTS));
+        assert_eq!(error(&text_of(&[INPUTS, OUTPUTS + 1])), format!("Line 1: network has to take {} inputs and give {} outputs", INPUTS, OUTPUTS));
+        assert_eq!(error(&format!("\n{} 0 {}\n\n{}", INPUTS, OUTPUTS, ["0"; OUTPUTS].join(" "))), "Line 2: layer with 0 neurons");
+
+        // Weights of older network with other layers
+        let text = text_of(&[INPUTS, 3, OUTPUTS]);
+        let first_layer = text.lines().nth(1).unwrap();
+        assert_eq!(error(&format!("{} 2 {}\n{}", INPUTS, OUTPUTS, first_layer)), format!("Line 2: layer needs {} weights, got {}", 2 * (INPUTS + 1), 3 * (INPUTS + 1)));
+        assert_eq!(error(&format!("{} 3 {}\n{}", INPUTS, OUTPUTS, first_layer)), "Missing layer of weights, file has 1 layers");
+        assert_eq!(error(&format!("{} 3 {}\n{}\n0 0 nan? 0", INPUTS, OUTPUTS, first_layer)), "Line 3: weight nan? is not a number");
+    }
+}
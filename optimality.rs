@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use crate::config::*;
+use crate::terrarium::Terrarium;
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// Comparison of pheromone trail to one food source with the best possible path
+pub struct GapSample {
+    pub tick: u64,
+    pub food: (i32, i32), // Grid position of food source
+    pub optimal: f32, // Cost of shortest path, soil costs digging_cost
+    pub trail: Option<f32>, // Cost of greedy maximum-pheromone trail, None if it doesn't reach the nest
+}
+
+impl GapSample {
+    pub fn ratio(&self) -> Option<f32> {
+        self.trail.map(|trail| trail / self.optimal.max(1.0))
+    }
+}
+
+// Field waiting in Dijkstra's queue, cheapest first
+struct Visit {
+    cost: f32,
+    x: i32,
+    y: i32,
+}
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+// Cost of stepping on the field - walking through tunnels or sky is cheap, digging through soil expensive
+fn step_cost(terrarium: &Terrarium, x: i32, y: i32) -> Option<f32> {
+    if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 || terrarium.mound.covers(x as usize, y as usize) ||
+        terrarium.obstacles[x as usize][y as usize] {
+        return None;
+    }
+    if terrarium.tunnels[x as usize][y as usize] || terrarium.is_sky(x, y) {
+        Some(1.0)
+    }
+    else {
+        Some(terrarium.params.digging_cost)
+    }
+}
+
+// Fields where ants hand over food, same range as scan_for_nest
+fn in_nest_area(terrarium: &Terrarium, x: i32, y: i32) -> bool {
+    let range = NEST_DETECTION_RANGE / WINDOW_TO_GAME_SCALE as i32;
+    (terrarium.nest.get_x_grid() - x).abs() + (terrarium.nest.get_y_grid() - y).abs() <= range
+}
+
+// Fields where ants pick food up, same range as scan_for_food
+fn in_food_area(food: (i32, i32), x: i32, y: i32) -> bool {
+    (food.0 - x).abs() + (food.1 - y).abs() <= FOOD_DETECTION_RANGE
+}
+
+// Cheapest cost of getting from nest area to every field (Dijkstra)
+pub fn costs_from_nest(terrarium: &Terrarium) -> Vec<Vec<f32>> {
+    let mut costs = vec![vec![f32::INFINITY; HEIGHT]; WIDTH];
+    let mut queue = BinaryHeap::new();
+
+    for x in 0..WIDTH as i32 {
+        for y in 0..HEIGHT as i32 {
+            if in_nest_area(terrarium, x, y) {
+                costs[x as usize][y as usize] = 0.0;
+                queue.push(Visit { cost: 0.0, x, y });
+            }
+        }
+    }
+
+    while let Some(Visit { cost, x, y }) = queue.pop() {
+        if cost > costs[x as usize][y as usize] {
+            continue; // Field was reached cheaper in the meantime
+        }
+        for (dx, dy) in DIRECTIONS {
+            let (nx, ny) = (x + dx, y + dy);
+            if let Some(step) = step_cost(terrarium, nx, ny) {
+                if cost + step < costs[nx as usize][ny as usize] {
+                    costs[nx as usize][ny as usize] = cost + step;
+                    queue.push(Visit { cost: cost + step, x: nx, y: ny });
+                }
+            }
+        }
+    }
+    costs
+}
+
+// Strongest pheromone on any edge leading to the field
+fn strongest_pheromone(terrarium: &Terrarium, x: i32, y: i32) -> f32 {
+    DIRECTIONS
+        .iter()
+        .filter(|(dx, dy)| step_cost(terrarium, x + dx, y + dy).is_some())
+        .map(|(dx, dy)| terrarium.pheromone_between(x, y, x + dx, y + dy))
+        .fold(0.0, f32::max)
+}
+
+// Strongest pheromone on edges leading from the field to fields not yet on the trail
+fn onward_pheromone(terrarium: &Terrarium, visited: &HashSet<(i32, i32)>, x: i32, y: i32) -> f32 {
+    DIRECTIONS
+        .iter()
+        .map(|(dx, dy)| (x + dx, y + dy))
+        .filter(|&(nx, ny)| step_cost(terrarium, nx, ny).is_some() && !visited.contains(&(nx, ny)))
+        .map(|(nx, ny)| terrarium.pheromone_between(x, y, nx, ny))
+        .fold(0.0, f32::max)
+}
+
+// Follows the strongest pheromone from food back to the nest (ants lay trails in this direction), turning back from side branches
+pub fn greedy_trail(terrarium: &Terrarium, food: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+    let start = (0..WIDTH as i32)
+        .flat_map(|x| (0..HEIGHT as i32).map(move |y| (x, y)))
+        .filter(|&(x, y)| in_food_area(food, x, y) && step_cost(terrarium, x, y).is_some())
+        .max_by(|a, b| strongest_pheromone(terrarium, a.0, a.1).total_cmp(&strongest_pheromone(terrarium, b.0, b.1)))?;
+
+    let mut trail = vec![start];
+    let mut visited = HashSet::from([start]);
+
+    while let Some(&(x, y)) = trail.last() {
+        if in_nest_area(terrarium, x, y) {
+            return Some(trail);
+        }
+
+        let next = DIRECTIONS
+            .iter()
+            .map(|(dx, dy)| (x + dx, y + dy))
+            .filter(|&(nx, ny)| step_cost(terrarium, nx, ny).is_some() && !visited.contains(&(nx, ny)))
+            .map(|(nx, ny)| ((nx, ny), terrarium.pheromone_between(x, y, nx, ny)))
+            .filter(|&(_, pheromone)| pheromone > MIN_PHEROMONES)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .or_else(|| {
+                // Ant stepping back forgets the field it returns to, so its trail misses one field there - it is stepped over
+                DIRECTIONS
+                    .iter()
+                    .map(|(dx, dy)| (x + dx, y + dy))
+                    .filter(|&(nx, ny)| step_cost(terrarium, nx, ny).is_some() && !visited.contains(&(nx, ny)))
+                    .map(|(nx, ny)| ((nx, ny), onward_pheromone(terrarium, &visited, nx, ny)))
+                    .filter(|&(_, pheromone)| pheromone > MIN_PHEROMONES)
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+            });
+
+        // Side branches of the trail lead nowhere, ant would turn back
+        match next {
+            Some((next, _)) => {
+                visited.insert(next);
+                trail.push(next);
+            }
+            None => {
+                trail.pop();
+            }
+        }
+    }
+
+    // Trail ends before reaching the nest
+    None
+}
+
+// Compares trails to all food sources with their shortest paths
+pub fn measure(terrarium: &Terrarium) -> Vec<GapSample> {
+    let costs = costs_from_nest(terrarium);
+
+    terrarium.food_sources
+        .iter()
+        .filter(|food| food.amount > 0)
+        .map(|food| {
+            let position = (food.position.get_x_grid(), food.position.get_y_grid());
+
+            let optimal = (0..WIDTH as i32)
+                .flat_map(|x| (0..HEIGHT as i32).map(move |y| (x, y)))
+                .filter(|&(x, y)| in_food_area(position, x, y))
+                .map(|(x, y)| costs[x as usize][y as usize])
+                .fold(f32::INFINITY, f32::min);
+
+            // Starting field is not paid for, same as in Dijkstra
+            let trail = greedy_trail(terrarium, position).map(|trail| {
+                trail[1..].iter().filter_map(|&(x, y)| step_cost(terrarium, x, y)).sum()
+            });
+
+            GapSample { tick: terrarium.tick, food: position, optimal, trail }
+        })
+        .collect()
+}
+
+// Average ratio of trail to optimal cost over food sources with trail, and number of such sources
+pub fn mean_ratio(samples: &[GapSample]) -> Option<(f32, usize)> {
+    let ratios: Vec<f32> = samples.iter().filter_map(GapSample::ratio).collect();
+    if ratios.is_empty() {
+        return None;
+    }
+    Some((ratios.iter().sum::<f32>() / ratios.len() as f32, ratios.len()))
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use crate::config::*;
+use crate::ants::Ant;
+use crate::functions::*;
+use crate::scenario::Scenario;
+use crate::terrarium::Terrarium;
+
+// Built-in experiments, top-down view
+pub const PRESETS: &[&str] = &["double_bridge_equal", "double_bridge_unequal", "double_bridge_short_later"];
+
+// Traffic on both branches of double bridge - branch 0 is the upper one, short in unequal variants
+pub struct BridgeMonitor {
+    gates: [(i32, i32); 2], // Middle field of every branch, ant stepping on it crosses the branch
+    on_gate: HashMap<u64, usize>,
+    pub crossings: [u64; 2],
+    window_start: [u64; 2], // Crossings when current sampling window started
+    pub samples: Vec<BridgeSample>,
+}
+
+pub struct BridgeSample {
+    pub tick: u64,
+    pub crossings: [u64; 2],
+    pub fraction: Option<f32>, // Share of upper branch on crossings in the window, None without any
+    pub model: f32, // Share expected by Deneubourg's model from crossings before the window
+}
+
+// Deneubourg's choice model - chance of taking upper branch after given crossings of both
+pub fn deneubourg(upper: u64, lower: u64) -> f32 {
+    let upper = (DENEUBOURG_K + upper as f32).powf(DENEUBOURG_N);
+    let lower = (DENEUBOURG_K + lower as f32).powf(DENEUBOURG_N);
+    upper / (upper + lower)
+}
+
+impl BridgeMonitor {
+
+    // Counts ants stepping on gates, called every tick
+    pub fn update(&mut self, ants: &[Ant], tick: u64) {
+        for ant in ants {
+            let position = (ant.position.get_x_grid(), ant.position.get_y_grid());
+            match self.gates.iter().position(|&gate| gate == position) {
+                Some(branch) => {
+                    if self.on_gate.insert(ant.id, branch) != Some(branch) {
+                        self.crossings[branch] += 1;
+                    }
+                }
+                None => {
+                    self.on_gate.remove(&ant.id);
+                }
+            }
+        }
+
+        if (tick + 1).is_multiple_of(BRIDGE_SAMPLE_INTERVAL) {
+            let upper = self.crossings[0] - self.window_start[0];
+            let lower = self.crossings[1] - self.window_start[1];
+            self.samples.push(BridgeSample {
+                tick,
+                crossings: self.crossings,
+                fraction: if upper + lower > 0 { Some(upper as f32 / (upper + lower) as f32) } else { None },
+                model: deneubourg(self.window_start[0], self.window_start[1]),
+            });
+            self.window_start = self.crossings;
+        }
+    }
+
+    pub fn latest(&self) -> Option<&BridgeSample> {
+        self.samples.last()
+    }
+
+    // Measured share of upper branch next to the model's expectation
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("tick,upper_crossings,lower_crossings,upper_fraction,model_fraction\n");
+        for sample in &self.samples {
+            let fraction = sample.fraction.map_or(String::new(), |fraction| fraction.to_string());
+            writeln!(csv, "{},{},{},{},{}", sample.tick, sample.crossings[0], sample.crossings[1], fraction, sample.model).unwrap();
+        }
+        csv
+    }
+}
+
+// Fields of corridor between two corners, both included
+fn corridor((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> Vec<(usize, usize)> {
+    (x1.min(x2)..=x1.max(x2))
+        .flat_map(|x| (y1.min(y2)..=y1.max(y2)).map(move |y| (x as usize, y as usize)))
+        .collect()
+}
+
+// Branch leaving the straight line at fork, bending by depth rows (negative goes up) and coming back at join
+fn branch(fork: (i32, i32), join: (i32, i32), depth: i32) -> Vec<(usize, usize)> {
+    let mut fields = corridor(fork, (fork.0, fork.1 + depth));
+    fields.extend(corridor((fork.0, fork.1 + depth), (join.0, join.1 + depth)));
+    fields.extend(corridor((join.0, join.1 + depth), join));
+    fields
+}
+
+// Double bridge between nest and one rich food source, everything except its tunnels is rock, None for unknown name
+pub fn build(name: &str, seed: u64) -> io::Result<Option<Terrarium>> {
+    let (upper_depth, lower_depth, upper_later) = match name {
+        "double_bridge_equal" => (BRIDGE_DEPTH_SHORT, BRIDGE_DEPTH_SHORT, false),
+        "double_bridge_unequal" => (BRIDGE_DEPTH_SHORT, BRIDGE_DEPTH_LONG, false),
+        "double_bridge_short_later" => (BRIDGE_DEPTH_SHORT, BRIDGE_DEPTH_LONG, true),
+        _ => return Ok(None),
+    };
+
+    let mut terrarium = Terrarium::with_seed(seed)?;
+    let nest = (terrarium.nest.get_x_grid(), terrarium.nest.get_y_grid());
+    let fork = (nest.0 + BRIDGE_STEM, nest.1);
+    let join = (fork.0 + BRIDGE_SPAN, nest.1);
+    let food = (join.0 + BRIDGE_STEM, nest.1);
+
+    // Nest chamber big enough for ants starting around the nest, stems and lower branch
+    let mut fields = corridor((nest.0 - 2, nest.1 - 2), (nest.0 + 2, nest.1 + 2));
+    fields.extend(corridor(nest, fork));
+    fields.extend(corridor(join, food));
+    fields.extend(branch(fork, join, lower_depth));
+    let upper = branch(fork, join, -upper_depth);
+    if !upper_later {
+        fields.extend(upper.iter().copied());
+    }
+
+    for column in terrarium.tunnels.iter_mut() {
+        column.fill(false);
+    }
+    for (x, y) in fields {
+        terrarium.set_tunnel(x, y, true);
+    }
+    for x in 0..WIDTH {
+        for y in 0..HEIGHT {
+            terrarium.obstacles[x][y] = !terrarium.tunnels[x][y];
+        }
+    }
+
+    // Upper branch is dug by scenario, one corridor after another
+    if upper_later {
+        let (top_fork, top_join) = ((fork.0, fork.1 - upper_depth), (join.0, join.1 - upper_depth));
+        let script = [(fork, top_fork), (top_fork, top_join), (top_join, join)]
+            .iter()
+            .map(|&(a, b)| format!("{} dig {} {} {} {}\n", BRIDGE_SHORT_LATER_TICK, a.0, a.1, b.0, b.1))
+            .collect::<String>();
+        terrarium.scenario = Scenario::parse(&script).expect("Preset scenario is valid");
+    }
+
+    // Only pheromones should change what ants choose
+    terrarium.fixed_tunnels = true;
+    terrarium.weather.random_rain = false;
+
+    let scale = WINDOW_TO_GAME_SCALE as i32;
+    terrarium.food_sources.clear();
+    terrarium.place_food(Position { x: food.0 * scale, y: food.1 * scale }, BRIDGE_FOOD_AMOUNT);
+
+    let middle = fork.0 + BRIDGE_SPAN / 2;
+    terrarium.bridge = Some(BridgeMonitor {
+        gates: [(middle, nest.1 - upper_depth), (middle, nest.1 + lower_depth)],
+        on_gate: HashMap::new(),
+        crossings: [0; 2],
+        window_start: [0; 2],
+        samples: Vec::new(),
+    });
+    Ok(Some(terrarium))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deneubourg_is_even_for_equal_crossings() {
+        for crossings in [0, 1, 20, 1000] {
+            assert!((deneubourg(crossings, crossings) - 0.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn deneubourg_is_symmetric() {
+        for (upper, lower) in [(0, 1), (5, 30), (100, 7)] {
+            assert!((deneubourg(upper, lower) + deneubourg(lower, upper) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn deneubourg_grows_with_upper_crossings() {
+        let mut previous = deneubourg(0, 10);
+        for upper in 1..200 {
+            let chance = deneubourg(upper, 10);
+            assert!(chance > previous);
+            assert!(chance > 0.0 && chance < 1.0);
+            previous = chance;
+        }
+
+        // Both branches stay possible even after long one sided traffic
+        assert!(deneubourg(0, 1000) > 0.0);
+        assert!(deneubourg(1000, 0) < 1.0);
+    }
+
+    #[test]
+    fn deneubourg_matches_model() {
+        let expected = (DENEUBOURG_K + 10.0).powf(DENEUBOURG_N) / ((DENEUBOURG_K + 10.0).powf(DENEUBOURG_N) + DENEUBOURG_K.powf(DENEUBOURG_N));
+        assert!((deneubourg(10, 0) - expected).abs() < 1e-6);
+    }
+}
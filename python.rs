@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use crate::config::*;
+use crate::behaviour::Move;
+use crate::chambers::ChamberRole;
+use crate::environment::{AgentStep, Environment, ObservationConfig};
+use crate::functions::*;
+use crate::presets;
+use crate::scenario::Scenario;
+use crate::stop::pheromone_entropy;
+use crate::terrarium::Terrarium;
+
+// Simulation driven from Python, lives in the thread which created it
+#[pyclass(unsendable)]
+pub struct World {
+    terrarium: Terrarium,
+}
+
+// Grid field from Python coordinates, IndexError outside the grid
+fn field(x: i64, y: i64) -> PyResult<(usize, usize)> {
+    if x < 0 || y < 0 || x >= WIDTH as i64 || y >= HEIGHT as i64 {
+        return Err(PyIndexError::new_err(format!("Field ({}, {}) is outside the {}x{} grid", x, y, WIDTH, HEIGHT)));
+    }
+    Ok((x as usize, y as usize))
+}
+
+#[pymethods]
+impl World {
+
+    // Config dict takes "seed", "preset", "speed" and names of runtime parameters, anything else is refused
+    #[new]
+    #[pyo3(signature = (config = None))]
+    fn new(config: Option<&Bound<'_, PyDict>>) -> PyResult<World> {
+        let Some(config) = config else {
+            return Ok(World { terrarium: Terrarium::new()? });
+        };
+
+        let seed = match config.get_item("seed")? {
+            Some(seed) => seed.extract::<u64>()?,
+            None => SEED,
+        };
+        let mut terrarium = match config.get_item("preset")? {
+            Some(name) => {
+                let name: String = name.extract()?;
+                presets::build(&name, seed)?.ok_or_else(|| PyValueError::new_err(format!("Unknown preset {}, expected one of {:?}", name, presets::PRESETS)))?
+            }
+            None => Terrarium::with_seed(seed)?,
+        };
+
+        // All values are checked before the world is handed over
+        for (key, value) in config.iter() {
+            let name: String = key.extract()?;
+            match name.as_str() {
+                "seed" | "preset" => {}
+                "speed" => terrarium.speed = value.extract::<u32>()?.clamp(1, MAX_SPEED),
+                _ if Params::NAMES.contains(&name.as_str()) => {
+                    terrarium.params.set(&name, value.extract::<f32>()?).map_err(PyValueError::new_err)?;
+                }
+                _ => return Err(PyValueError::new_err(format!("Unknown config key {}", name))),
+            }
+        }
+        Ok(World { terrarium })
+    }
+
+    // Runs given number of ticks, pause of front ends doesn't apply here
+    #[pyo3(signature = (ticks = 1))]
+    fn step(&mut self, ticks: u64) -> u64 {
+        for _ in 0..ticks {
+            self.terrarium.step();
+        }
+        self.terrarium.tick
+    }
+
+    #[getter]
+    fn tick(&self) -> u64 {
+        self.terrarium.tick
+    }
+
+    // Pheromones on edges, shape (WIDTH, 2 * HEIGHT) - see board_to_pheromones for indexing
+    fn pheromones<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f32>> {
+        let pheromones = &self.terrarium.pheromones;
+        Array2::from_shape_fn((WIDTH, 2 * HEIGHT), |(x, y)| pheromones[x][y]).into_pyarray(py)
+    }
+
+    // Dug fields, shape (WIDTH, HEIGHT)
+    fn tunnels<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<bool>> {
+        let tunnels = &self.terrarium.tunnels;
+        Array2::from_shape_fn((WIDTH, HEIGHT), |(x, y)| tunnels[x][y]).into_pyarray(py)
+    }
+
+    // Obstacles, shape (WIDTH, HEIGHT)
+    fn obstacles<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<bool>> {
+        let obstacles = &self.terrarium.obstacles;
+        Array2::from_shape_fn((WIDTH, HEIGHT), |(x, y)| obstacles[x][y]).into_pyarray(py)
+    }
+
+    // Every ant as dict with its grid position and what it's doing
+    fn ants<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let ants = PyList::empty(py);
+        for ant in &self.terrarium.ants {
+            let state = PyDict::new(py);
+            state.set_item("id", ant.id)?;
+            state.set_item("x", ant.position.get_x_grid())?;
+            state.set_item("y", ant.position.get_y_grid())?;
+            state.set_item("returning", ant.returning)?;
+            state.set_item("carrying_food", ant.carrying_food)?;
+
+            // Own parameters, inherited from a successful forager
+            let params = ant.params(&self.terrarium);
+            state.set_item("alpha", params.desirability_pheromones)?;
+            state.set_item("beta", params.desirability_heuristics)?;
+            state.set_item("soil_limit", params.ant_soil_limit)?;
+            ants.append(state)?;
+        }
+        Ok(ants)
+    }
+
+    // Food sources as dicts with grid position and amount left
+    fn food<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let food = PyList::empty(py);
+        for source in &self.terrarium.food_sources {
+            let state = PyDict::new(py);
+            state.set_item("x", source.position.get_x_grid())?;
+            state.set_item("y", source.position.get_y_grid())?;
+            state.set_item("amount", source.amount)?;
+            food.append(state)?;
+        }
+        Ok(food)
+    }
+
+    // Returns false if the field is too close to the nest
+    fn add_food(&mut self, x: i64, y: i64) -> PyResult<bool> {
+        let (x, y) = field(x, y)?;
+        let scale = WINDOW_TO_GAME_SCALE as i32;
+        Ok(self.terrarium.spawn_food(Position { x: x as i32 * scale, y: y as i32 * scale }))
+    }
+
+    fn remove_food(&mut self, x: i64, y: i64) -> PyResult<bool> {
+        let (x, y) = field(x, y)?;
+        Ok(self.terrarium.remove_food(x as i32, y as i32))
+    }
+
+    fn add_obstacle(&mut self, x: i64, y: i64) -> PyResult<()> {
+        let (x, y) = field(x, y)?;
+        self.terrarium.set_obstacle(x, y, true);
+        Ok(())
+    }
+
+    fn remove_obstacle(&mut self, x: i64, y: i64) -> PyResult<()> {
+        let (x, y) = field(x, y)?;
+        self.terrarium.set_obstacle(x, y, false);
+        Ok(())
+    }
+
+    // Scenario file runs from the current tick on, replacing any earlier one
+    fn load_scenario(&mut self, path: &str) -> PyResult<()> {
+        self.terrarium.scenario = Scenario::load(path).map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(())
+    }
+
+    fn get_param(&self, name: &str) -> PyResult<f32> {
+        self.terrarium.params.get(name).ok_or_else(|| PyValueError::new_err(format!("Unknown parameter {}", name)))
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> PyResult<()> {
+        self.terrarium.params.set(name, value).map_err(PyValueError::new_err)
+    }
+
+    // Same numbers as GET /metrics of the control server, without food sources
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let terrarium = &self.terrarium;
+        let metrics = PyDict::new(py);
+        metrics.set_item("tick", terrarium.tick)?;
+        metrics.set_item("ants", terrarium.ants.len())?;
+        metrics.set_item("delivered", terrarium.delivered)?;
+        metrics.set_item("excavated", terrarium.excavated)?;
+        metrics.set_item("refilled", terrarium.refilled)?;
+        metrics.set_item("decayed", terrarium.decayed)?;
+        metrics.set_item("mound", terrarium.mound.volume)?;
+        metrics.set_item("stored_food", terrarium.colony.total(ChamberRole::Storage))?;
+        metrics.set_item("brood", terrarium.colony.total(ChamberRole::Brood))?;
+        metrics.set_item("waste", terrarium.colony.total(ChamberRole::Midden))?;
+        metrics.set_item("raining", terrarium.weather.is_raining())?;
+        metrics.set_item("entropy", pheromone_entropy(&terrarium.pheromones))?;
+        metrics.set_item("optimality", terrarium.latest_optimality().map(|(ratio, _, _)| ratio))?;
+
+        if let Some(sample) = terrarium.bridge.as_ref().and_then(|bridge| bridge.latest()) {
+            metrics.set_item("bridge_crossings", sample.crossings)?;
+            metrics.set_item("bridge_upper_fraction", sample.fraction)?;
+            metrics.set_item("bridge_model_fraction", sample.model)?;
+        }
+        metrics.set_item("scenario_executed", terrarium.scenario.executed.len())?;
+        metrics.set_item("scenario_pending", terrarium.scenario.pending())?;
+
+        // Mean and standard deviation of every heritable trait
+        let traits = PyDict::new(py);
+        for (name, mean, deviation) in terrarium.trait_distribution() {
+            traits.set_item(name, (mean, deviation))?;
+        }
+        metrics.set_item("traits", traits)?;
+        Ok(metrics)
+    }
+}
+
+// Learning environment for Python agents, observations, rewards and dones are dicts keyed by ant id
+#[pyclass(unsendable)]
+pub struct Env {
+    environment: Environment,
+}
+
+type Steps<'py> = (Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyDict>);
+type Transition<'py> = (Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyDict>, bool);
+
+// Splits steps of ants into dicts of observations (NumPy arrays), rewards and dones
+fn steps_to_dicts(py: Python<'_>, steps: Vec<AgentStep>) -> PyResult<Steps<'_>> {
+    let (observations, rewards, dones) = (PyDict::new(py), PyDict::new(py), PyDict::new(py));
+    for step in steps {
+        observations.set_item(step.id, step.observation.into_pyarray(py))?;
+        rewards.set_item(step.id, step.reward)?;
+        dones.set_item(step.id, step.done)?;
+    }
+    Ok((observations, rewards, dones))
+}
+
+#[pymethods]
+impl Env {
+
+    // Config dict takes parts of observation (pheromones, moves, radius, food, nest, state) and names of runtime parameters
+    #[new]
+    #[pyo3(signature = (config = None))]
+    fn new(config: Option<&Bound<'_, PyDict>>) -> PyResult<Env> {
+        let mut observation = ObservationConfig::default();
+        let mut params = Params::default();
+
+        if let Some(config) = config {
+            for (key, value) in config.iter() {
+                let name: String = key.extract()?;
+                match name.as_str() {
+                    "pheromones" => observation.pheromones = value.extract()?,
+                    "moves" => observation.moves = value.extract()?,
+                    "radius" => observation.radius = value.extract()?,
+                    "food" => observation.food = value.extract()?,
+                    "nest" => observation.nest = value.extract()?,
+                    "state" => observation.state = value.extract()?,
+                    _ if Params::NAMES.contains(&name.as_str()) => {
+                        params.set(&name, value.extract::<f32>()?).map_err(PyValueError::new_err)?;
+                    }
+                    _ => return Err(PyValueError::new_err(format!("Unknown config key {}", name))),
+                }
+            }
+        }
+
+        let mut environment = Environment::new(observation)?;
+        environment.terrarium.params = params;
+        Ok(Env { environment })
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        self.environment.config.size()
+    }
+
+    // Moves are 0 left, 1 right, 2 up, 3 down and 4 stay
+    #[getter]
+    fn action_count(&self) -> usize {
+        Move::ALL.len()
+    }
+
+    #[getter]
+    fn tick(&self) -> u64 {
+        self.environment.terrarium.tick
+    }
+
+    // New episode, returns observations of all ants
+    #[pyo3(signature = (seed = SEED))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> PyResult<Bound<'py, PyDict>> {
+        let (observations, _, _) = steps_to_dicts(py, self.environment.reset(seed)?)?;
+        Ok(observations)
+    }
+
+    // One tick, ants without action wait - returns observations, rewards, dones and whether the episode ended
+    fn step<'py>(&mut self, py: Python<'py>, actions: HashMap<u64, usize>) -> PyResult<Transition<'py>> {
+        let mut moves = HashMap::new();
+        for (id, action) in actions {
+            let chosen = Move::ALL.get(action).ok_or_else(|| PyValueError::new_err(format!("Invalid action {} of ant {}", action, id)))?;
+            moves.insert(id, *chosen);
+        }
+
+        let (observations, rewards, dones) = steps_to_dicts(py, self.environment.step(moves))?;
+        Ok((observations, rewards, dones, self.environment.is_finished()))
+    }
+}
+
+// Module imported as `ants`, name has to match the library
+#[pymodule]
+fn ants(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<World>()?;
+    module.add_class::<Env>()?;
+    module.add("WIDTH", WIDTH)?;
+    module.add("HEIGHT", HEIGHT)?;
+    Ok(())
+}
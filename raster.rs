@@ -0,0 +1,108 @@
+use crate::config::*;
+use crate::chambers::ChamberRole;
+use crate::clock::*;
+use crate::terrarium::Terrarium;
+
+// Mixes colour over another one
+fn blend(base: (f32, f32, f32), over: (f32, f32, f32), alpha: f32) -> (f32, f32, f32) {
+    (
+        base.0 + (over.0 - base.0) * alpha,
+        base.1 + (over.1 - base.1) * alpha,
+        base.2 + (over.2 - base.2) * alpha,
+    )
+}
+
+// Paints square of fields centred on the position given in window coordinates
+fn fill_square(pixels: &mut [(f32, f32, f32)], x: i32, y: i32, size: f32, color: (f32, f32, f32), alpha: f32) {
+    let half = (size / WINDOW_TO_GAME_SCALE / 2.0) as i32;
+    let (cx, cy) = (x / WINDOW_TO_GAME_SCALE as i32, y / WINDOW_TO_GAME_SCALE as i32);
+
+    for px in (cx - half).max(0)..(cx + half).min(WIDTH as i32) {
+        for py in (cy - half).max(0)..(cy + half).min(HEIGHT as i32) {
+            let index = py as usize * WIDTH + px as usize;
+            pixels[index] = blend(pixels[index], color, alpha);
+        }
+    }
+}
+
+// Draws the terrarium without window, one pixel for every field, row after row
+// Used by front ends which can't use the GPU
+pub fn rasterise(terrarium: &Terrarium) -> Vec<(u8, u8, u8)> {
+    let mut pixels = vec![(0.0, 0.0, 0.0); WIDTH * HEIGHT];
+
+    for x in 0..WIDTH {
+        for y in 0..HEIGHT {
+            let mut color = SOIL_TYPES[terrarium.soil[x][y] as usize].color;
+
+            if SIDE_VIEW && y < SURFACE_ROW {
+                color = (0.53, 0.75, 0.92);
+            }
+            if terrarium.tunnels[x][y] {
+                color = (0.4, 0.2, 0.1);
+            }
+            if terrarium.obstacles[x][y] {
+                color = (0.5, 0.5, 0.5);
+            }
+
+            // Mound
+            if terrarium.mound.covers(x, y) {
+                color = (0.561, 0.361, 0.231);
+            }
+            else if !SIDE_VIEW && terrarium.mound.heights[x][y] > 0 {
+                color = blend(color, (0.561, 0.361, 0.231), (0.3 + 0.1 * terrarium.mound.heights[x][y] as f32).min(1.0));
+            }
+
+            // Water
+            let water = terrarium.weather.water[x][y];
+            if water > 0.0 {
+                color = blend(color, (0.2, 0.4, 0.9), 0.3 + 0.6 * water);
+            }
+
+            // Pheromones on edges going left and down from the field
+            let pheromone = terrarium.pheromones[x][2 * y].max(terrarium.pheromones[x][2 * y + 1]);
+            if pheromone > MIN_PHEROMONES {
+                color = blend(color, (1.0, 1.0, 1.0), pheromone / MAX_PHEROMONES);
+            }
+
+            pixels[y * WIDTH + x] = color;
+        }
+    }
+
+    // Dug parts of chambers
+    for chamber in &terrarium.colony.chambers {
+        let role_color = match chamber.role {
+            ChamberRole::Storage => (0.2, 0.6, 0.2),
+            ChamberRole::Brood => (0.9, 0.85, 0.6),
+            ChamberRole::Midden => (0.35, 0.3, 0.25),
+        };
+        for &(x, y) in &chamber.fields[..chamber.dug] {
+            pixels[y * WIDTH + x] = blend(pixels[y * WIDTH + x], role_color, 0.5);
+        }
+    }
+
+    // Nest and food
+    fill_square(&mut pixels, terrarium.nest.x, terrarium.nest.y, NEST_SIZE, (0.141, 0.090, 0.078), 1.0);
+    for food in terrarium.food_sources.iter().filter(|food| food.amount > 0) {
+        let alpha = if food.diurnal && is_night(terrarium.tick) { 0.3 } else { 1.0 };
+        fill_square(&mut pixels, food.position.x, food.position.y, FOOD_SCALE, (0.0, 1.0, 0.0), alpha);
+    }
+
+    // Ants
+    for ant in &terrarium.ants {
+        let (x, y) = (ant.position.get_x_grid(), ant.position.get_y_grid());
+        if x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT {
+            pixels[y as usize * WIDTH + x as usize] = ant.color();
+        }
+    }
+
+    // Darken scene during night
+    let darkness = (-daylight(terrarium.tick)).max(0.0) * 0.5;
+
+    pixels
+        .into_iter()
+        .map(|color| {
+            let (r, g, b) = blend(color, (0.05, 0.05, 0.2), darkness);
+            ((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+        })
+        .collect()
+}
@@ -0,0 +1,100 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageResult, RgbaImage};
+use crate::config::*;
+use crate::functions::has_flag;
+use crate::raster::rasterise;
+use crate::terrarium::Terrarium;
+
+// Saves rasterised frames as PNG sequence or one animated GIF
+pub struct Recorder {
+    pub directory: PathBuf,
+    pub saved: u64,
+    frame: u64,
+    gif: Option<GifEncoder<BufWriter<File>>>,
+    first_tick: Option<u64>,
+    scenario_written: usize, // Scenario actions already noted in scenario.txt
+}
+
+impl Recorder {
+
+    // Creates first free directory recordings/run_000, run_001, ...
+    pub fn start() -> ImageResult<Recorder> {
+        let mut run = 0;
+        let directory = loop {
+            let directory = PathBuf::from(RECORD_DIR).join(format!("run_{:03}", run));
+            if !directory.exists() {
+                break directory;
+            }
+            run += 1;
+        };
+        fs::create_dir_all(&directory)?;
+
+        let gif = if RECORD_GIF || has_flag("--gif") {
+            let mut encoder = GifEncoder::new(BufWriter::new(File::create(directory.join("run.gif"))?));
+            encoder.set_repeat(Repeat::Infinite)?;
+            Some(encoder)
+        } else {
+            None
+        };
+
+        Ok(Recorder { directory, saved: 0, frame: 0, gif, first_tick: None, scenario_written: 0 })
+    }
+
+    // Called for every rendered frame, only every RECORD_EVERY-th is saved
+    pub fn capture(&mut self, terrarium: &Terrarium) -> ImageResult<()> {
+        self.frame += 1;
+        if !(self.frame - 1).is_multiple_of(RECORD_EVERY) {
+            return Ok(());
+        }
+
+        // Every field becomes square of RECORD_SCALE pixels
+        let pixels = rasterise(terrarium);
+        let image = RgbaImage::from_fn(WIDTH as u32 * RECORD_SCALE, HEIGHT as u32 * RECORD_SCALE, |x, y| {
+            let (r, g, b) = pixels[(y / RECORD_SCALE) as usize * WIDTH + (x / RECORD_SCALE) as usize];
+            image::Rgba([r, g, b, 255])
+        });
+
+        match &mut self.gif {
+            Some(encoder) => encoder.encode_frame(Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(GIF_FRAME_DELAY_MS, 1)))?,
+            None => image.save(self.directory.join(format!("frame_{:05}.png", self.saved)))?,
+        }
+        self.saved += 1;
+        self.note_scenario(terrarium)?;
+        Ok(())
+    }
+
+    // Scenario actions which happened during recording go to scenario.txt with the frame showing them first
+    fn note_scenario(&mut self, terrarium: &Terrarium) -> io::Result<()> {
+        let first_tick = *self.first_tick.get_or_insert(terrarium.tick);
+        let executed = &terrarium.scenario.executed;
+        if self.scenario_written >= executed.len() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(self.directory.join("scenario.txt"))?;
+        for (tick, action) in &executed[self.scenario_written..] {
+            if *tick >= first_tick {
+                writeln!(file, "frame {} tick {}: {}", self.saved - 1, tick, action)?;
+            }
+        }
+        self.scenario_written = executed.len();
+        Ok(())
+    }
+
+    // Line for status of front ends
+    pub fn status(&self) -> String {
+        format!("Recording to {}: {} frames", self.directory.display(), self.saved)
+    }
+}
+
+// Starts new recording or finishes running one (GIF is completed when encoder is dropped)
+pub fn toggle(recorder: &mut Option<Recorder>) -> ImageResult<()> {
+    *recorder = match recorder.take() {
+        Some(_) => None,
+        None => Some(Recorder::start()?),
+    };
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use crate::config::*;
+use crate::events::Event;
+use crate::functions::*;
+use crate::terrarium::Terrarium;
+
+// One change of the world planned in scenario, positions are grid fields
+pub enum Action {
+    Food { x: i32, y: i32, amount: usize },
+    RemoveFood { index: usize }, // Index in current list of food sources, as in metrics
+    Block { x1: usize, y1: usize, x2: usize, y2: usize }, // Obstacles over rectangle, corners included, fields with ants stay open
+    Unblock { x1: usize, y1: usize, x2: usize, y2: usize },
+    Dig { x1: usize, y1: usize, x2: usize, y2: usize }, // Ready tunnel, also through obstacles
+    Param { name: String, value: f32 },
+    Kill { fraction: f32 },
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Food { x, y, amount } => write!(f, "food {} at ({}, {})", amount, x, y),
+            Action::RemoveFood { index } => write!(f, "food source {} removed", index),
+            Action::Block { x1, y1, x2, y2 } => write!(f, "blocked ({}, {}) to ({}, {})", x1, y1, x2, y2),
+            Action::Unblock { x1, y1, x2, y2 } => write!(f, "unblocked ({}, {}) to ({}, {})", x1, y1, x2, y2),
+            Action::Dig { x1, y1, x2, y2 } => write!(f, "dug ({}, {}) to ({}, {})", x1, y1, x2, y2),
+            Action::Param { name, value } => write!(f, "{} set to {}", name, value),
+            Action::Kill { fraction } => write!(f, "{:.0}% of ants killed", fraction * 100.0),
+        }
+    }
+}
+
+// Timed actions executed at the start of their tick, in order of the file
+#[derive(Default)]
+pub struct Scenario {
+    actions: Vec<(u64, Action)>,
+    next: usize,
+    pub executed: Vec<(u64, String)>, // Tick and description of every executed action
+}
+
+// Number from scenario line, error names the line
+fn number<T: std::str::FromStr>(words: &[&str], index: usize, line: usize) -> Result<T, String> {
+    words.get(index)
+        .ok_or_else(|| format!("Line {}: missing argument", line))?
+        .parse()
+        .map_err(|_| format!("Line {}: {} is not a valid number", line, words[index]))
+}
+
+// Rectangle inside the grid, corners in any order
+fn rectangle(words: &[&str], line: usize) -> Result<(usize, usize, usize, usize), String> {
+    let (x1, y1, x2, y2): (usize, usize, usize, usize) = (number(words, 2, line)?, number(words, 3, line)?, number(words, 4, line)?, number(words, 5, line)?);
+    if x1.max(x2) >= WIDTH || y1.max(y2) >= HEIGHT {
+        return Err(format!("Line {}: rectangle is outside the {}x{} grid", line, WIDTH, HEIGHT));
+    }
+    Ok((x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)))
+}
+
+impl Scenario {
+
+    // One action per line - "<tick> food <x> <y> <amount>", "<tick> remove_food <index>", "<tick> block <x1> <y1> <x2> <y2>",
+    // "<tick> unblock <x1> <y1> <x2> <y2>", "<tick> dig <x1> <y1> <x2> <y2>", "<tick> param <name> <value>" or "<tick> kill <fraction>",
+    // # starts comment
+    pub fn parse(text: &str) -> Result<Scenario, String> {
+        let mut actions = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let words: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            let tick: u64 = number(&words, 0, line_number)?;
+            let action = match words.get(1).copied() {
+                Some("food") => {
+                    let (x, y): (i32, i32) = (number(&words, 2, line_number)?, number(&words, 3, line_number)?);
+                    if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 {
+                        return Err(format!("Line {}: food is outside the {}x{} grid", line_number, WIDTH, HEIGHT));
+                    }
+                    let amount = if words.len() > 4 { number(&words, 4, line_number)? } else { FOOD_AMOUNT_PER_SOURCE };
+                    Action::Food { x, y, amount }
+                }
+                Some("remove_food") => Action::RemoveFood { index: number(&words, 2, line_number)? },
+                Some("block") => {
+                    let (x1, y1, x2, y2) = rectangle(&words, line_number)?;
+                    Action::Block { x1, y1, x2, y2 }
+                }
+                Some("unblock") => {
+                    let (x1, y1, x2, y2) = rectangle(&words, line_number)?;
+                    Action::Unblock { x1, y1, x2, y2 }
+                }
+                Some("dig") => {
+                    let (x1, y1, x2, y2) = rectangle(&words, line_number)?;
+                    Action::Dig { x1, y1, x2, y2 }
+                }
+                Some("param") => {
+                    let name = words.get(2).ok_or_else(|| format!("Line {}: missing parameter name", line_number))?.to_string();
+                    let value = number(&words, 3, line_number)?;
+                    Params::default().set(&name, value).map_err(|error| format!("Line {}: {}", line_number, error))?;
+                    Action::Param { name, value }
+                }
+                Some("kill") => {
+                    let fraction: f32 = number(&words, 2, line_number)?;
+                    if !(0.0..=1.0).contains(&fraction) {
+                        return Err(format!("Line {}: fraction of ants has to be between 0 and 1", line_number));
+                    }
+                    Action::Kill { fraction }
+                }
+                Some(other) => return Err(format!("Line {}: unknown action {}", line_number, other)),
+                None => return Err(format!("Line {}: missing action", line_number)),
+            };
+            actions.push((tick, action));
+        }
+
+        // Stable sort keeps order of actions planned for the same tick
+        actions.sort_by_key(|&(tick, _)| tick);
+        Ok(Scenario { actions, next: 0, executed: Vec::new() })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Scenario> {
+        Scenario::parse(&fs::read_to_string(path)?).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn pending(&self) -> usize {
+        self.actions.len() - self.next
+    }
+
+    // Executes every action planned up to this tick, noted in the event log
+    pub fn run(&mut self, terrarium: &mut Terrarium) {
+        while let Some((tick, action)) = self.actions.get(self.next) {
+            if *tick > terrarium.tick {
+                break;
+            }
+            self.next += 1;
+
+            let done = match action {
+                Action::Food { x, y, amount } => {
+                    let scale = WINDOW_TO_GAME_SCALE as i32;
+                    terrarium.place_food(Position { x: x * scale, y: y * scale }, *amount);
+                    true
+                }
+                Action::RemoveFood { index } => {
+                    let exists = *index < terrarium.food_sources.len();
+                    if exists {
+                        terrarium.food_sources.remove(*index);
+                    }
+                    exists
+                }
+                Action::Block { x1, y1, x2, y2 } => {
+
+                    // Ants are never walled into rock
+                    let occupied: HashSet<(i32, i32)> = terrarium.ants.iter().map(|ant| (ant.position.get_x_grid(), ant.position.get_y_grid())).collect();
+                    for x in *x1..=*x2 {
+                        for y in *y1..=*y2 {
+                            if !occupied.contains(&(x as i32, y as i32)) {
+                                terrarium.set_obstacle(x, y, true);
+                            }
+                        }
+                    }
+                    true
+                }
+                Action::Unblock { x1, y1, x2, y2 } => {
+                    for x in *x1..=*x2 {
+                        for y in *y1..=*y2 {
+                            terrarium.set_obstacle(x, y, false);
+                        }
+                    }
+                    true
+                }
+                Action::Dig { x1, y1, x2, y2 } => {
+                    for x in *x1..=*x2 {
+                        for y in *y1..=*y2 {
+                            terrarium.set_obstacle(x, y, false);
+                            terrarium.set_tunnel(x, y, true);
+                        }
+                    }
+                    true
+                }
+                Action::Param { name, value } => terrarium.params.set(name, *value).is_ok(),
+                Action::Kill { fraction } => {
+
+                    // Victims are chosen by seed and tick, so the run stays reproducible
+                    let mut rng = StdRng::seed_from_u64(stream_seed(terrarium.seed, terrarium.tick, Stream::ScenarioKill, 0));
+                    let count = (terrarium.ants.len() as f32 * fraction).round() as usize;
+                    let mut victims = sample(&mut rng, terrarium.ants.len(), count).into_vec();
+                    victims.sort_unstable();
+                    for victim in victims.into_iter().rev() {
+                        terrarium.ants.remove(victim);
+                    }
+                    true
+                }
+            };
+
+            let description = if done { action.to_string() } else { format!("{} failed", action) };
+            terrarium.events.push(terrarium.tick, Event::Scenario { action: description.clone() });
+            self.executed.push((terrarium.tick, description));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        Scenario::parse(text).err().unwrap()
+    }
+
+    fn descriptions(scenario: &Scenario) -> Vec<(u64, String)> {
+        scenario.actions.iter().map(|(tick, action)| (*tick, action.to_string())).collect()
+    }
+
+    #[test]
+    fn parse_reads_every_action() {
+        let scenario = Scenario::parse("500 food 40 20 100\n600 food 1 2\n2000 remove_food 1\n2500 block 20 12 10 10\n2600 unblock 10 10 20 12\n2700 dig 10 10 20 10\n3000 param evaporation_rate_fast 0.8\n3500 kill 0.3").unwrap();
+        assert_eq!(descriptions(&scenario), vec![
+            (500, "food 100 at (40, 20)".to_string()),
+            (600, format!("food {} at (1, 2)", FOOD_AMOUNT_PER_SOURCE)),
+            (2000, "food source 1 removed".to_string()),
+            (2500, "blocked (10, 10) to (20, 12)".to_string()),
+            (2600, "unblocked (10, 10) to (20, 12)".to_string()),
+            (2700, "dug (10, 10) to (20, 10)".to_string()),
+            (3000, "evaporation_rate_fast set to 0.8".to_string()),
+            (3500, "30% of ants killed".to_string()),
+        ]);
+        assert_eq!(scenario.pending(), 8);
+    }
+
+    #[test]
+    fn parse_skips_comments_and_sorts_by_tick() {
+        let scenario = Scenario::parse("# Whole line comment\n\n300 kill 0.5 # after action\n100 remove_food 2\n300 remove_food 0\n100 kill 1").unwrap();
+        assert_eq!(descriptions(&scenario), vec![
+            (100, "food source 2 removed".to_string()),
+            (100, "100% of ants killed".to_string()),
+            (300, "50% of ants killed".to_string()),
+            (300, "food source 0 removed".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn parse_names_line_of_error() {
+        assert_eq!(error("# comment\n\nx food 1 1"), "Line 3: x is not a valid number");
+        assert_eq!(error("10 kill 0.5\n20"), "Line 2: missing action");
+        assert_eq!(error("10 flood 1 1"), "Line 1: unknown action flood");
+        assert_eq!(error("10 food 1"), "Line 1: missing argument");
+        assert_eq!(error("10 remove_food -1"), "Line 1: -1 is not a valid number");
+        assert_eq!(error("10 block 1 1 2"), "Line 1: missing argument");
+        assert_eq!(error("10 dig 1 1 2 two"), "Line 1: two is not a valid number");
+        assert_eq!(error("10 kill"), "Line 1: missing argument");
+        assert_eq!(error("10 kill 1.5"), "Line 1: fraction of ants has to be between 0 and 1");
+        assert_eq!(error("10 param"), "Line 1: missing parameter name");
+        assert_eq!(error("10 param evaporation_rate_fast"), "Line 1: missing argument");
+        assert!(error("10 param wind 0.5").starts_with("Line 1: "));
+        assert!(error("10 param evaporation_rate_fast -1").starts_with("Line 1: "));
+    }
+
+    #[test]
+    fn parse_refuses_fields_outside_grid() {
+        let outside = format!("Line 1: rectangle is outside the {}x{} grid", WIDTH, HEIGHT);
+        assert_eq!(error(&format!("10 block 0 0 {} 1", WIDTH)), outside);
+        assert_eq!(error(&format!("10 unblock 0 {} 1 1", HEIGHT)), outside);
+        assert_eq!(error(&format!("10 dig {} 0 0 0", WIDTH)), outside);
+        let food = format!("Line 1: food is outside the {}x{} grid", WIDTH, HEIGHT);
+        assert_eq!(error("10 food -1 5"), food);
+        assert_eq!(error(&format!("10 food 5 {}", HEIGHT)), food);
+    }
+
+    #[test]
+    fn block_leaves_fields_with_ants_open() {
+        let mut terrarium = Terrarium::with_seed(1).unwrap();
+        let scale = WINDOW_TO_GAME_SCALE as i32;
+        terrarium.ants.truncate(1);
+        terrarium.ants[0].position = Position { x: 5 * scale, y: 6 * scale };
+
+        let mut scenario = Scenario::parse(&format!("{} block 4 5 6 7", terrarium.tick)).unwrap();
+        scenario.run(&mut terrarium);
+        for x in 4..=6 {
+            for y in 5..=7 {
+                assert_eq!(terrarium.obstacles[x][y], (x, y) != (5, 6));
+            }
+        }
+        assert_eq!(scenario.pending(), 0);
+    }
+}
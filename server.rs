@@ -0,0 +1,348 @@
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use serde_json::{json, Map, Value};
+use tiny_http::{Header, Method, Request, Response};
+use crate::config::*;
+use crate::chambers::ChamberRole;
+use crate::clock::clock;
+use crate::functions::*;
+use crate::stop::pheromone_entropy;
+use crate::terrarium::Terrarium;
+
+// Request of client waiting for the simulation thread
+enum Command {
+    Call { method: Method, path: String, body: Value, reply: Sender<(u16, Value)> },
+    Subscribe(Sender<String>),
+}
+
+// Local HTTP server controlling the simulation with JSON, requests are answered between frames
+pub struct Server {
+    commands: Receiver<Command>,
+    subscribers: Vec<Sender<String>>,
+    last_tick: u64,
+    last_tunnels: Vec<Vec<bool>>, // State subscribers already know
+    last_pheromones: Vec<Vec<f32>>,
+}
+
+// Body of streamed response, one JSON message per line
+struct ChannelReader {
+    lines: Receiver<String>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.buffer.len() {
+            match self.lines.recv() {
+                Ok(line) => {
+                    self.buffer = (line + "\n").into_bytes();
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0), // Simulation ended
+            }
+        }
+        let count = out.len().min(self.buffer.len() - self.position);
+        out[..count].copy_from_slice(&self.buffer[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+fn json_header() -> Header {
+    Header::from_bytes("Content-Type", "application/json").unwrap()
+}
+
+// Runs on server thread, passes request to the simulation and sends its answer back
+fn handle(mut request: Request, commands: &Sender<Command>) {
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+
+    // Stream lives in its own thread, so other requests aren't blocked
+    if *request.method() == Method::Get && path == "/stream" {
+        let (sender, lines) = mpsc::channel();
+        if commands.send(Command::Subscribe(sender)).is_ok() {
+            thread::spawn(move || {
+                let reader = ChannelReader { lines, buffer: Vec::new(), position: 0 };
+                let header = Header::from_bytes("Content-Type", "application/x-ndjson").unwrap();
+                let _ = request.respond(Response::new(200.into(), vec![header], reader, None, None));
+            });
+        }
+        return;
+    }
+
+    let mut text = String::new();
+    let body = match request.as_reader().read_to_string(&mut text) {
+        Ok(_) if text.trim().is_empty() => Ok(Value::Null),
+        Ok(_) => serde_json::from_str(&text).map_err(|error| error.to_string()),
+        Err(error) => Err(error.to_string()),
+    };
+
+    let (status, value) = match body {
+        Err(error) => (400, json!({ "error": error })),
+        Ok(body) => {
+            let (reply, answer) = mpsc::channel();
+            let method = request.method().clone();
+            match commands.send(Command::Call { method, path, body, reply }) {
+                Ok(()) => answer.recv_timeout(Duration::from_millis(SERVER_TIMEOUT_MS)).unwrap_or((503, json!({ "error": "Simulation is not responding" }))),
+                Err(_) => (503, json!({ "error": "Simulation ended" })),
+            }
+        }
+    };
+
+    let response = Response::from_string(value.to_string()).with_status_code(status).with_header(json_header());
+    let _ = request.respond(response);
+}
+
+// Grid position from body like {"x": 10, "y": 20}
+fn grid_position(body: &Value) -> Option<(i32, i32)> {
+    let x = i32::try_from(body.get("x")?.as_i64()?).ok()?;
+    let y = i32::try_from(body.get("y")?.as_i64()?).ok()?;
+    if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 {
+        return None;
+    }
+    Some((x, y))
+}
+
+fn params_json(params: &Params) -> Value {
+    Params::NAMES.iter().map(|&name| (name.to_string(), json!(params.get(name)))).collect::<Map<String, Value>>().into()
+}
+
+fn ants_json(terrarium: &Terrarium) -> Value {
+    // 0 - looking around, 1 - carrying soil, 2 - carrying food
+    terrarium.ants
+        .iter()
+        .map(|ant| json!([ant.id, ant.position.get_x_grid(), ant.position.get_y_grid(), if ant.carrying_food { 2 } else { ant.returning as u8 }]))
+        .collect()
+}
+
+fn food_json(terrarium: &Terrarium) -> Value {
+    terrarium.food_sources
+        .iter()
+        .map(|food| json!({ "x": food.position.get_x_grid(), "y": food.position.get_y_grid(), "amount": food.amount, "diurnal": food.diurnal }))
+        .collect()
+}
+
+// Everything worth plotting, cheap enough to ask for every frame
+pub fn metrics_json(terrarium: &Terrarium) -> Value {
+    json!({
+        "tick": terrarium.tick,
+        "time": clock(terrarium.tick),
+        "paused": terrarium.is_paused(),
+        "speed": terrarium.speed,
+        "ants": terrarium.ants.len(),
+        "delivered": terrarium.delivered,
+        "excavated": terrarium.excavated,
+        "refilled": terrarium.refilled,
+        "decayed": terrarium.decayed,
+        "mound": terrarium.mound.volume,
+        "stored_food": terrarium.colony.total(ChamberRole::Storage),
+        "brood": terrarium.colony.total(ChamberRole::Brood),
+        "waste": terrarium.colony.total(ChamberRole::Midden),
+        "raining": terrarium.weather.is_raining(),
+        "entropy": pheromone_entropy(&terrarium.pheromones),
+        "traits": terrarium.trait_distribution()
+            .into_iter()
+            .map(|(name, mean, deviation)| (name.to_string(), json!({ "mean": mean, "deviation": deviation })))
+            .collect::<Map<String, Value>>(),
+        "scenario": json!({
+            "executed": terrarium.scenario.executed.len(),
+            "pending": terrarium.scenario.pending(),
+            "last": terrarium.scenario.executed.last().map(|(tick, action)| json!({ "tick": tick, "action": action })),
+        }),
+        "bridge": terrarium.bridge.as_ref().and_then(|bridge| bridge.latest()).map(|sample| json!({
+            "crossings": sample.crossings,
+            "upper_fraction": sample.fraction,
+            "model_fraction": sample.model,
+        })),
+        "optimality": terrarium.latest_optimality().map(|(ratio, trails, foods)| json!({ "ratio": ratio, "trails": trails, "foods": foods })),
+        "food": food_json(terrarium),
+    })
+}
+
+// Answers one request, returns HTTP status and JSON body
+fn route(terrarium: &mut Terrarium, method: &Method, path: &str, body: &Value) -> (u16, Value) {
+    match (method, path) {
+        (Method::Get, "/metrics") => (200, metrics_json(terrarium)),
+        (Method::Post, "/pause") => {
+            terrarium.set_paused(true);
+            (200, json!({ "paused": true, "tick": terrarium.tick }))
+        }
+        (Method::Post, "/resume") => {
+            terrarium.set_paused(false);
+            (200, json!({ "paused": false, "tick": terrarium.tick }))
+        }
+        (Method::Post, "/step") => {
+            let ticks = body.get("ticks").and_then(Value::as_u64).unwrap_or(1);
+            if ticks > SERVER_MAX_STEP {
+                return (400, json!({ "error": format!("At most {} ticks can be stepped at once", SERVER_MAX_STEP) }));
+            }
+            for _ in 0..ticks {
+                terrarium.step();
+            }
+            (200, json!({ "tick": terrarium.tick }))
+        }
+        (Method::Get, "/params") => (200, params_json(&terrarium.params)),
+        (Method::Post, "/params") => {
+            let Some(values) = body.as_object() else {
+                return (400, json!({ "error": "Expected object of parameter names and values" }));
+            };
+
+            // All values are checked before any is changed
+            let mut params = terrarium.params;
+            for (name, value) in values {
+                let Some(value) = value.as_f64() else {
+                    return (400, json!({ "error": format!("Value of {} is not a number", name) }));
+                };
+                if let Err(error) = params.set(name, value as f32) {
+                    return (400, json!({ "error": error }));
+                }
+            }
+            terrarium.params = params;
+            (200, params_json(&terrarium.params))
+        }
+        (Method::Post, "/food") => match grid_position(body) {
+            Some((x, y)) => {
+                let scale = WINDOW_TO_GAME_SCALE as i32;
+                let placed = terrarium.spawn_food(Position { x: x * scale, y: y * scale });
+                (if placed { 200 } else { 409 }, json!({ "placed": placed, "food": food_json(terrarium) }))
+            }
+            None => (400, json!({ "error": "Expected x and y inside the grid" })),
+        },
+        (Method::Delete, "/food") => match grid_position(body) {
+            Some((x, y)) => {
+                let removed = terrarium.remove_food(x, y);
+                (if removed { 200 } else { 404 }, json!({ "removed": removed, "food": food_json(terrarium) }))
+            }
+            None => (400, json!({ "error": "Expected x and y inside the grid" })),
+        },
+        _ => (404, json!({ "error": format!("Unknown endpoint {} {}", method, path) })),
+    }
+}
+
+impl Server {
+
+    // Starts listening on SERVER_ADDRESS, requests wait until the front end calls poll
+    pub fn start() -> io::Result<Server> {
+        let http = tiny_http::Server::http(SERVER_ADDRESS).map_err(io::Error::other)?;
+        let (commands, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for request in http.incoming_requests() {
+                handle(request, &commands);
+            }
+        });
+
+        Ok(Server {
+            commands: receiver,
+            subscribers: Vec::new(),
+            last_tick: 0,
+            last_tunnels: vec![vec![false; HEIGHT]; WIDTH],
+            last_pheromones: vec![vec![MIN_PHEROMONES; 2 * HEIGHT]; WIDTH],
+        })
+    }
+
+    // Answers all waiting requests, called once per frame
+    pub fn poll(&mut self, terrarium: &mut Terrarium) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                Command::Call { method, path, body, reply } => {
+                    let _ = reply.send(route(terrarium, &method, &path, &body));
+                }
+                Command::Subscribe(sender) => {
+
+                    // Others get what changed so far, so everyone knows the same state afterwards
+                    self.broadcast(terrarium);
+                    self.last_tunnels.clone_from(&terrarium.tunnels);
+                    self.last_pheromones.clone_from(&terrarium.pheromones);
+                    self.last_tick = terrarium.tick;
+                    if sender.send(self.full_state(terrarium).to_string()).is_ok() {
+                        self.subscribers.push(sender);
+                    }
+                }
+            }
+        }
+    }
+
+    // Whole state for new subscriber, later it gets only diffs
+    fn full_state(&self, terrarium: &Terrarium) -> Value {
+        let tunnels: Vec<Value> = (0..WIDTH)
+            .flat_map(|x| (0..HEIGHT).map(move |y| (x, y)))
+            .filter(|&(x, y)| terrarium.tunnels[x][y])
+            .map(|(x, y)| json!([x, y]))
+            .collect();
+        let pheromones: Vec<Value> = (0..WIDTH)
+            .flat_map(|x| (0..2 * HEIGHT).map(move |y| (x, y)))
+            .filter(|&(x, y)| terrarium.pheromones[x][y] > MIN_PHEROMONES)
+            .map(|(x, y)| json!([x, y, terrarium.pheromones[x][y]]))
+            .collect();
+
+        json!({
+            "type": "full",
+            "tick": terrarium.tick,
+            "width": WIDTH,
+            "height": HEIGHT,
+            "nest": [terrarium.nest.get_x_grid(), terrarium.nest.get_y_grid()],
+            "tunnels": tunnels,
+            "pheromones": pheromones,
+            "ants": ants_json(terrarium),
+            "food": food_json(terrarium),
+        })
+    }
+
+    // Sends subscribers ants, changed tunnels and pheromones which changed noticeably since last message
+    pub fn broadcast(&mut self, terrarium: &Terrarium) {
+        if self.subscribers.is_empty() || terrarium.tick == self.last_tick {
+            return;
+        }
+
+        let mut tunnels = Vec::new();
+        let mut pheromones = Vec::new();
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                if terrarium.tunnels[x][y] != self.last_tunnels[x][y] {
+                    self.last_tunnels[x][y] = terrarium.tunnels[x][y];
+                    tunnels.push(json!([x, y, terrarium.tunnels[x][y]]));
+                }
+            }
+            for y in 0..2 * HEIGHT {
+                if (terrarium.pheromones[x][y] - self.last_pheromones[x][y]).abs() > SERVER_PHEROMONE_DELTA {
+                    self.last_pheromones[x][y] = terrarium.pheromones[x][y];
+                    pheromones.push(json!([x, y, terrarium.pheromones[x][y]]));
+                }
+            }
+        }
+        self.last_tick = terrarium.tick;
+
+        let diff = json!({
+            "type": "diff",
+            "tick": terrarium.tick,
+            "ants": ants_json(terrarium),
+            "tunnels": tunnels,
+            "pheromones": pheromones,
+            "food": food_json(terrarium),
+        })
+        .to_string();
+
+        // Closed streams are forgotten
+        self.subscribers.retain(|subscriber| subscriber.send(diff.clone()).is_ok());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_position_refuses_fields_outside_grid() {
+        assert_eq!(grid_position(&json!({"x": 4, "y": 5})), Some((4, 5)));
+        assert_eq!(grid_position(&json!({"x": WIDTH - 1, "y": HEIGHT - 1})), Some((WIDTH as i32 - 1, HEIGHT as i32 - 1)));
+        assert_eq!(grid_position(&json!({"x": 4294967300u64, "y": 5})), None);
+        assert_eq!(grid_position(&json!({"x": 4, "y": -4294967291i64})), None);
+        assert_eq!(grid_position(&json!({"x": -1, "y": 5})), None);
+        assert_eq!(grid_position(&json!({"x": WIDTH, "y": 5})), None);
+        assert_eq!(grid_position(&json!({"x": 1.5, "y": 5})), None);
+        assert_eq!(grid_position(&json!({"y": 5})), None);
+    }
+}
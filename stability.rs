@@ -0,0 +1,85 @@
+use crate::config::*;
+use crate::functions::*;
+use crate::mound::Mound;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+
+// Generates soil types of the whole grid - layers in side view, patches in top-down view
+pub fn generate_soil(seed: u64) -> Vec<Vec<u8>> {
+    let mut soil = vec![vec![0; HEIGHT]; WIDTH];
+
+    for (x, column) in soil.iter_mut().enumerate() {
+        for (y, field) in column.iter_mut().enumerate() {
+            let patch = if SIDE_VIEW {
+                splitmix64(seed ^ (y / SOIL_PATCH_SIZE) as u64)
+            } else {
+                splitmix64(seed ^ splitmix64((x / SOIL_PATCH_SIZE) as u64) ^ (y / SOIL_PATCH_SIZE) as u64)
+            };
+            *field = (patch % SOIL_TYPES.len() as u64) as u8;
+        }
+    }
+
+    soil
+}
+
+// Counts open fields in 5x5 area around the field
+fn open_around(tunnels: &[Vec<bool>], x: usize, y: usize) -> usize {
+    tunnels[x.saturating_sub(2)..(x + 3).min(WIDTH)]
+        .iter()
+        .map(|column| column[y.saturating_sub(2)..(y + 3).min(HEIGHT)].iter().filter(|&&open| open).count())
+        .sum()
+}
+
+// Soil lying above the field - in side view everything up to the surface, in top-down view the mound
+fn overburden(tunnels: &[Vec<bool>], mound: &Mound, x: usize, y: usize) -> u32 {
+    if SIDE_VIEW {
+        let buried = (SURFACE_ROW..y).filter(|&above| !tunnels[x][above]).count() as u32;
+        buried + mound.heights[x][0]
+    } else {
+        mound.heights[x][y]
+    }
+}
+
+// Nest and its chambers never collapse, decay or flood
+pub(crate) fn is_protected(nest: Position, chambers: &[Vec<bool>], x: usize, y: usize) -> bool {
+    let dx = (nest.get_x_grid() - x as i32).abs();
+    let dy = (nest.get_y_grid() - y as i32).abs();
+    (dx + dy) * WINDOW_TO_GAME_SCALE as i32 <= NEST_DETECTION_RANGE || chambers[x][y]
+}
+
+// Finds tunnels collapsing in this tick, every field rolls its own dice so the result doesn't depend on threads
+pub fn find_collapses(tunnels: &[Vec<bool>], soil: &[Vec<u8>], mound: &Mound, nest: Position, chambers: &[Vec<bool>], seed: u64, tick: u64) -> Vec<(usize, usize)> {
+    (0..WIDTH)
+        .into_par_iter()
+        .flat_map_iter(|x| {
+            (0..HEIGHT)
+                .filter(move |&y| tunnels[x][y] && !is_protected(nest, chambers, x, y))
+                .filter(move |&y| {
+                    let soil_type = &SOIL_TYPES[soil[x][y] as usize];
+                    let unstable = open_around(tunnels, x, y) > soil_type.max_open ||
+                        overburden(tunnels, mound, x, y) > soil_type.max_overburden;
+
+                    unstable && StdRng::seed_from_u64(stream_seed(seed, tick, Stream::Collapse, (x * HEIGHT + y) as u64)).gen::<f32>() < soil_type.collapse_chance
+                })
+                .map(move |y| (x, y))
+        })
+        .collect()
+}
+
+// Finds tunnels nobody walked through for too long
+pub fn find_decayed(tunnels: &[Vec<bool>], last_visit: &[Vec<u64>], nest: Position, chambers: &[Vec<bool>], tick: u64) -> Vec<(usize, usize)> {
+    if TUNNEL_DECAY_TICKS == 0 {
+        return Vec::new();
+    }
+
+    (0..WIDTH)
+        .into_par_iter()
+        .flat_map_iter(|x| {
+            (0..HEIGHT)
+                .filter(move |&y| tunnels[x][y] && !is_protected(nest, chambers, x, y))
+                .filter(move |&y| tick.saturating_sub(last_visit[x][y]) > TUNNEL_DECAY_TICKS)
+                .map(move |y| (x, y))
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+use crate::config::*;
+use crate::events::Event;
+use crate::terrarium::Terrarium;
+
+// Why headless run ended
+pub enum StopReason {
+    TickLimit,
+    FoodGone,
+    DeliveryStable { rate: f32 },
+    Stagnation { entropy: f32 },
+    WallClock { seconds: f32 },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::TickLimit => write!(f, "tick limit reached"),
+            StopReason::FoodGone => write!(f, "all food gone"),
+            StopReason::DeliveryStable { rate } => write!(f, "delivery rate stable at {:.4} food per tick", rate),
+            StopReason::Stagnation { entropy } => write!(f, "trails stagnated (pheromone entropy {:.3})", entropy),
+            StopReason::WallClock { seconds } => write!(f, "wall clock limit reached after {:.1} s", seconds),
+        }
+    }
+}
+
+// Normalised Shannon entropy of pheromones above minimum - 1 when spread evenly, near 0 when all on few edges
+pub fn pheromone_entropy(pheromones: &[Vec<f32>]) -> Option<f32> {
+    let amounts: Vec<f32> = pheromones.iter().flatten().map(|&pheromone| pheromone - MIN_PHEROMONES).filter(|&amount| amount > 0.0).collect();
+    if amounts.len() < 2 {
+        return None;
+    }
+
+    let total: f32 = amounts.iter().sum();
+    let entropy: f32 = amounts.iter().map(|&amount| amount / total).map(|p| -p * p.ln()).sum();
+    Some(entropy / (amounts.len() as f32).ln())
+}
+
+// Watches the run and tells when it should end
+pub struct StopConditions {
+    started: Instant,
+    deliveries: VecDeque<(u64, u64)>, // Tick and food delivered until then, for last DELIVERY_RATE_WINDOW ticks
+    rates: VecDeque<(u64, f32)>, // Delivery rates measured during last DELIVERY_STABLE_TICKS ticks
+}
+
+impl StopConditions {
+
+    pub fn new() -> Self {
+        StopConditions { started: Instant::now(), deliveries: VecDeque::new(), rates: VecDeque::new() }
+    }
+
+    // Called after every tick, on stagnation pheromones can be reset instead of stopping (as in MAX-MIN Ant System)
+    pub fn check(&mut self, terrarium: &mut Terrarium) -> Option<StopReason> {
+        let seconds = self.started.elapsed().as_secs_f32();
+        if MAX_WALL_CLOCK_SECONDS > 0.0 && seconds >= MAX_WALL_CLOCK_SECONDS {
+            return Some(StopReason::WallClock { seconds });
+        }
+        if HEADLESS_TICKS != 0 && terrarium.tick >= HEADLESS_TICKS {
+            return Some(StopReason::TickLimit);
+        }
+        if STOP_CHECK_INTERVAL == 0 || !terrarium.tick.is_multiple_of(STOP_CHECK_INTERVAL) {
+            return None;
+        }
+
+        if STOP_WHEN_FOOD_GONE && terrarium.food_sources.iter().all(|food| food.amount == 0) {
+            return Some(StopReason::FoodGone);
+        }
+
+        if DELIVERY_STABLE_TICKS != 0 {
+            if let Some(rate) = self.delivery_rate(terrarium) {
+                return Some(StopReason::DeliveryStable { rate });
+            }
+        }
+
+        if STAGNATION_ENTROPY > 0.0 {
+            if let Some(entropy) = pheromone_entropy(&terrarium.pheromones).filter(|&entropy| entropy < STAGNATION_ENTROPY) {
+                if !RESET_ON_STAGNATION {
+                    return Some(StopReason::Stagnation { entropy });
+                }
+                terrarium.reset_pheromones();
+                terrarium.events.push(terrarium.tick, Event::PheromonesReset { entropy });
+            }
+        }
+
+        None
+    }
+
+    // Rate over the last window, returned only when it stayed within DELIVERY_EPSILON long enough
+    fn delivery_rate(&mut self, terrarium: &Terrarium) -> Option<f32> {
+        let tick = terrarium.tick;
+        self.deliveries.push_back((tick, terrarium.delivered));
+        while self.deliveries.front().is_some_and(|&(since, _)| since + DELIVERY_RATE_WINDOW < tick) {
+            self.deliveries.pop_front();
+        }
+
+        // Not enough history yet
+        let &(since, delivered) = self.deliveries.front()?;
+        if since + DELIVERY_RATE_WINDOW > tick {
+            return None;
+        }
+        let rate = (terrarium.delivered - delivered) as f32 / DELIVERY_RATE_WINDOW as f32;
+
+        self.rates.push_back((tick, rate));
+        while self.rates.front().is_some_and(|&(since, _)| since + DELIVERY_STABLE_TICKS < tick) {
+            self.rates.pop_front();
+        }
+
+        // Colony which delivers nothing isn't converged, it just hasn't found food yet
+        let (lowest, highest) = self.rates.iter().fold((f32::MAX, f32::MIN), |(low, high), &(_, rate)| (low.min(rate), high.max(rate)));
+        let covered = self.rates.front().is_some_and(|&(since, _)| since + DELIVERY_STABLE_TICKS <= tick);
+        if covered && lowest > 0.0 && highest - lowest <= DELIVERY_EPSILON {
+            return Some(rate);
+        }
+        None
+    }
+}
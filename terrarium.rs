@@ -1,39 +1,79 @@
-use ggez::graphics::{DrawMode, DrawParam, Rect, Text, TextFragment};
-use ggez::graphics::Mesh;
 use crate::config::*;
 use crate::ants::Ant;
-use ggez::graphics::*;
-use ggez::{Context, GameError, GameResult};
-use ggez::event::MouseButton;
-use ggez::input::keyboard::{KeyCode, KeyInput};
-use rand::Rng;
+use crate::behaviour::*;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::io;
 use crate::functions::*;
-use ggez::mint::Point2;
+use crate::mound::Mound;
+use crate::events::*;
+use crate::stability::*;
+use crate::chambers::*;
+use crate::weather::Weather;
+use crate::clock::*;
+use crate::export;
+use crate::optimality::{self, GapSample};
+use crate::scenario::Scenario;
+use crate::presets::BridgeMonitor;
 
 pub struct Terrarium {
 
     // Technical
-    pre_rendered_tunnel: Mesh,
     state: GameState,
-    nest: Position,
-    food_amount: i32,
+    pub speed: u32, // Ticks per frame
+    pub nest: Position,
+    pub(crate) seed: u64,
+    pub tick: u64,
+    pub excavated: u64, // Fields ever dug
+    pub refilled: u64,  // Dead ends filled back with soil
+    pub decayed: u64,   // Unused tunnels filled back with soil
+    pub delivered: u64, // Food brought to the nest
+    next_ant_id: u64,
+    pub events: EventLog,
+    pub params: Params,
+    pub optimality: Vec<GapSample>, // Trail to optimal path comparisons over time
+    foragers: VecDeque<Traits>, // Traits of ants which latest brought food
+    pub scenario: Scenario,
+    pub bridge: Option<BridgeMonitor>, // Branch traffic of double bridge presets
+    pub fixed_tunnels: bool, // Tunnels never collapse or decay, experiments keep their layout
+    changed: Vec<(usize, usize)>, // Fields whose tunnel, obstacle, mound or water changed since front end took them
+    changed_mask: Vec<Vec<bool>>, // Field is already in the list, so the list never outgrows the grid
 
     // Entities
-    ants: Vec<Ant>,
+    behaviours: Vec<Box<dyn AntBehavior>>,
+    pub ants: Vec<Ant>,
     pub pheromones: Vec<Vec<f32>>,
     pub tunnels: Vec<Vec<bool>>,
+    pub obstacles: Vec<Vec<bool>>, // Rocks ants can neither dig nor walk through
+    pub soil: Vec<Vec<u8>>, // Index of soil type of every field
+    pub traffic: Vec<Vec<u32>>, // How many times ants walked through every field
+    pub last_visit: Vec<Vec<u64>>, // Tick in which some ant was last on the field
+    pub rubble: Vec<Vec<bool>>, // Collapsed tunnels, ants prefer digging them until they're open again
+    pub mound: Mound,
+    pub colony: Colony,
+    pub weather: Weather,
     pub food_sources: Vec<FoodSource>,
 }
 
 impl Terrarium {
 
     // Initialization of the terrarium
-    pub fn new(ctx: &Context) -> Terrarium {
+    pub fn new() -> io::Result<Terrarium> {
+        Terrarium::with_seed(SEED)
+    }
+
+    // Terrarium with its own soil, food and ants, same seed gives same run
+    // Fails when behaviour of some caste can't be created, e.g. weights of neural ants are unreadable
+    pub fn with_seed(seed: u64) -> io::Result<Terrarium> {
 
         // Boring technical stuff
-        let mut rng = rand::thread_rng();
-        let nest_pos = Position { x: (WINDOW_WIDTH / 2.0) as i32, y: (WINDOW_HEIGHT / 2.0) as i32 };
-        let pre_rendered_tunnel = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0.0, 0.0, WINDOW_TO_GAME_SCALE, WINDOW_TO_GAME_SCALE), Color::new(0.4, 0.2, 0.1, 1.0)).unwrap();
+        let mut rng = StdRng::seed_from_u64(seed);
+        // In side view nest entrance sits on the surface
+        let nest_y = if SIDE_VIEW { SURFACE_ROW as f32 * WINDOW_TO_GAME_SCALE } else { WINDOW_HEIGHT / 2.0 };
+        let nest_pos = Position { x: (WINDOW_WIDTH / 2.0) as i32, y: nest_y as i32 };
+        let soil = generate_soil(seed);
 
         // Generate random food sources
         let mut food_sources = Vec::new();
@@ -42,7 +82,10 @@ impl Terrarium {
             // Until valid coordinates has been found
             loop {
                 let food_x = rng.gen_range((FOOD_SCALE/WINDOW_TO_GAME_SCALE) as usize..WIDTH- (FOOD_SCALE/WINDOW_TO_GAME_SCALE) as usize);
-                let food_y = rng.gen_range((FOOD_SCALE/WINDOW_TO_GAME_SCALE) as usize..HEIGHT-(FOOD_SCALE/WINDOW_TO_GAME_SCALE) as usize);
+                let mut food_y = rng.gen_range((FOOD_SCALE/WINDOW_TO_GAME_SCALE) as usize..HEIGHT-(FOOD_SCALE/WINDOW_TO_GAME_SCALE) as usize);
+
+                // In side view food lies on the surface
+                if SIDE_VIEW { food_y = SURFACE_ROW - 1; }
 
                 let dx = (nest_pos.get_x_grid() - food_x as i32).abs();
                 let dy = (nest_pos.get_y_grid() - food_y as i32).abs();
@@ -52,28 +95,68 @@ impl Terrarium {
                         x: (food_x * WINDOW_TO_GAME_SCALE as usize) as i32,
                         y: (food_y * WINDOW_TO_GAME_SCALE as usize) as i32,
                     };
-                    food_sources.push(FoodSource { position, amount: FOOD_AMOUNT_PER_SOURCE, });
+                    let diurnal = rng.gen::<f32>() < DIURNAL_FOOD_SHARE;
+                    food_sources.push(FoodSource { position, amount: FOOD_AMOUNT_PER_SOURCE, diurnal, });
                     break;
                 }
             }
         }
 
+        // Behaviour of every caste
+        let behaviours: Vec<Box<dyn AntBehavior>> = CASTES.iter().map(|(name, _)| behaviour_by_name(name)).collect::<io::Result<_>>()?;
+
         // Generate ants
         let mut ants = Vec::new();
-        for _ in 0..ANT_COUNT { ants.push(Ant::new(&nest_pos)); }
+        for id in 0..ANT_COUNT as u64 {
+            ants.push(Ant::new(id, caste_of(id), &nest_pos, &mut rng));
+        }
 
         // Return object terrarium
-        Terrarium {
-            pre_rendered_tunnel,
+        let mut terrarium = Terrarium {
             state: GameState::Playing,
+            speed: 1,
             nest: nest_pos,
-            food_amount: 0,
-
+            seed,
+            tick: 0,
+            excavated: 0,
+            refilled: 0,
+            decayed: 0,
+            delivered: 0,
+            next_ant_id: ANT_COUNT as u64,
+            events: EventLog::new(),
+            params: Params::default(),
+            optimality: Vec::new(),
+            foragers: VecDeque::new(),
+            scenario: Scenario::default(),
+            bridge: None,
+            fixed_tunnels: false,
+            changed: Vec::new(),
+            changed_mask: vec![vec![false; HEIGHT]; WIDTH],
+
+            behaviours,
             ants,
             pheromones: vec![vec![MIN_PHEROMONES; 2 * HEIGHT]; WIDTH],
             tunnels: vec![vec![false; HEIGHT]; WIDTH],
+            obstacles: vec![vec![false; HEIGHT]; WIDTH],
+            soil,
+            traffic: vec![vec![0; HEIGHT]; WIDTH],
+            last_visit: vec![vec![0; HEIGHT]; WIDTH],
+            rubble: vec![vec![false; HEIGHT]; WIDTH],
+            mound: Mound::new(),
+            colony: Colony::new(nest_pos),
+            weather: Weather::new(),
             food_sources,
+        };
+
+        // In side view ants start from shaft below entrance
+        if SIDE_VIEW {
+            let x = nest_pos.get_x_grid() as usize;
+            for y in SURFACE_ROW..(SURFACE_ROW + ENTRANCE_DEPTH).min(HEIGHT) {
+                terrarium.set_tunnel(x, y, true);
+            }
         }
+
+        Ok(terrarium)
     }
 
     // Gets neighbours of given ant
@@ -90,9 +173,12 @@ impl Terrarium {
             (position.get_x_grid(), position.get_y_grid() + 1), // Right
         ];
 
-        // Add only moves that are in the window
+        // Add only moves that are in the window, in side view ant can't enter loose soil or hang in the air
+        // Flooded fields and obstacles can't be entered at all
         for (nx, ny) in potential_moves {
-            if nx >= 0 && ny >= 0 && (nx as usize) < WIDTH && (ny as usize) < HEIGHT {
+            if nx >= 0 && ny >= 0 && (nx as usize) < WIDTH && (ny as usize) < HEIGHT &&
+                !self.mound.covers(nx as usize, ny as usize) && self.is_supported(nx, ny) &&
+                !self.weather.is_flooded(nx as usize, ny as usize) && !self.obstacles[nx as usize][ny as usize] {
                 neighbors.push(Position { x: nx * WINDOW_TO_GAME_SCALE as i32, y: ny * WINDOW_TO_GAME_SCALE as i32 });
             }
         }
@@ -100,14 +186,14 @@ impl Terrarium {
         neighbors
     }
 
-    // Return food source only if food is nearby
-    pub fn scan_for_food(&mut self, pos: Position) -> Option<&mut FoodSource> {
-        for food in self.food_sources.iter_mut() {
+    // Return index of food source only if food is nearby
+    pub fn scan_for_food(&self, pos: Position) -> Option<usize> {
+        for (index, food) in self.food_sources.iter().enumerate() {
             let dx = (food.position.get_x_grid() - pos.get_x_grid()).abs();
             let dy = (food.position.get_y_grid() - pos.get_y_grid()).abs();
 
-            if dx + dy <= FOOD_DETECTION_RANGE && food.amount > 0 {
-                return Some(food)
+            if dx + dy <= FOOD_DETECTION_RANGE && food.amount > 0 && !(food.diurnal && self.is_night()) {
+                return Some(index)
             }
         }
 
@@ -124,13 +210,223 @@ impl Terrarium {
         else { false }
     }
 
-    // Return bool whether position is tunnel
+    pub fn is_night(&self) -> bool {
+        is_night(self.tick)
+    }
+
+    // Returns behaviour of given caste
+    pub fn behaviour(&self, caste: usize) -> &dyn AntBehavior {
+        self.behaviours[caste].as_ref()
+    }
+
+    // Replaces behaviour of given caste with custom one
+    pub fn set_behaviour(&mut self, caste: usize, behaviour: Box<dyn AntBehavior>) {
+        self.behaviours[caste] = behaviour;
+    }
+
+    // Return bool whether position is tunnel, sky of side view is always open
     pub fn is_tunnel(&self, position: Position) -> bool {
-        self.tunnels[position.get_x_grid() as usize][position.get_y_grid() as usize]
+        let (x, y) = (position.get_x_grid(), position.get_y_grid());
+        self.tunnels[x as usize][y as usize] || self.is_sky(x, y)
+    }
+
+    // Side view only, open air above the surface
+    pub fn is_sky(&self, x: i32, y: i32) -> bool {
+        SIDE_VIEW && y < SURFACE_ROW as i32 && !self.mound.covers(x as usize, y as usize)
+    }
+
+    // Something ant can hold on to - soil, tunnel walls and loose soil of the mound
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 {
+            return false;
+        }
+        if SIDE_VIEW && y < SURFACE_ROW as i32 {
+            return self.mound.covers(x as usize, y as usize);
+        }
+        !self.tunnels[x as usize][y as usize]
+    }
+
+    // In side view ant can't hang in the air, it needs something solid next to it
+    pub fn is_supported(&self, x: i32, y: i32) -> bool {
+        !SIDE_VIEW || [(x, y + 1), (x - 1, y), (x + 1, y), (x, y - 1)].into_iter().any(|(sx, sy)| self.is_solid(sx, sy))
+    }
+
+    // Digs or fills tunnel, remembers the change for rendering
+    pub fn set_tunnel(&mut self, x: usize, y: usize, active: bool) {
+        if active {
+            self.rubble[x][y] = false;
+        }
+        if self.tunnels[x][y] != active {
+            self.tunnels[x][y] = active;
+            self.mark_changed(x, y);
+        }
+    }
+
+    // Remembers field for front ends, every field at most once
+    fn mark_changed(&mut self, x: usize, y: usize) {
+        if !self.changed_mask[x][y] {
+            self.changed_mask[x][y] = true;
+            self.changed.push((x, y));
+        }
+    }
+
+    // Fields changed since last call, front end redraws only them
+    pub fn take_changed(&mut self) -> Vec<(usize, usize)> {
+        for &(x, y) in &self.changed {
+            self.changed_mask[x][y] = false;
+        }
+        std::mem::take(&mut self.changed)
+    }
+
+    // Digs tunnel, returns false if it was already there
+    pub fn dig_tunnel(&mut self, x: usize, y: usize) -> bool {
+        if self.tunnels[x][y] {
+            return false;
+        }
+        self.set_tunnel(x, y, true);
+        self.excavated += 1;
+        self.last_visit[x][y] = self.tick;
+        true
+    }
+
+    // Counts ant walking through the field
+    pub fn record_visit(&mut self, position: Position) {
+        let (x, y) = (position.get_x_grid(), position.get_y_grid());
+        if x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT {
+            self.traffic[x as usize][y as usize] += 1;
+            self.last_visit[x as usize][y as usize] = self.tick;
+        }
+    }
+
+    // Fills tunnel back with carried soil
+    pub fn fill_tunnel(&mut self, x: usize, y: usize) {
+        self.set_tunnel(x, y, false);
+        self.refilled += 1;
+    }
+
+    // Checks if tunnel leads nowhere - it has only one tunnel neighbour and isn't part of the nest or its chambers,
+    // no ant stands in it and nobody dug or walked into it for DEAD_END_MIN_AGE ticks
+    pub fn is_dead_end(&self, position: Position) -> bool {
+        let (x, y) = (position.get_x_grid() as usize, position.get_y_grid() as usize);
+        self.tunnels[x][y] && !self.colony.planned[x][y] && !self.scan_for_nest(position) &&
+            self.tick.saturating_sub(self.last_visit[x][y]) > DEAD_END_MIN_AGE &&
+            self.get_all_neighbors(position).into_iter().filter(|&neighbour| self.is_tunnel(neighbour)).count() == 1
+    }
+
+    // Piles soil at one of the dump sites next to nest
+    pub fn dump_soil(&mut self, site: usize, amount: i32) {
+        let (dx, dy) = if DUMP_SITES.is_empty() { (0, 0) } else { DUMP_SITES[site % DUMP_SITES.len()] };
+        let x = (self.nest.get_x_grid() + dx).clamp(0, WIDTH as i32 - 1) as usize;
+        let y = (self.nest.get_y_grid() + dy).clamp(0, HEIGHT as i32 - 1) as usize;
+
+        for _ in 0..amount {
+            let (x, y) = self.mound.deposit(x, y);
+            self.mark_changed(x, y);
+            self.drop_loose_soil(x);
+        }
+    }
+
+    // Side view only, grain lying over open tunnel falls down and fills the lowest open field below it
+    fn drop_loose_soil(&mut self, x: usize) {
+        if !SIDE_VIEW || !self.tunnels[x][SURFACE_ROW] || !self.mound.remove(x, 0) {
+            return;
+        }
+        self.mark_changed(x, 0);
+        let mut y = SURFACE_ROW;
+        while y + 1 < HEIGHT && self.tunnels[x][y + 1] {
+            y += 1;
+        }
+        self.fill_tunnel(x, y);
+    }
+
+    // Pheromone on edge between two neighbouring fields
+    pub fn pheromone_between(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> f32 {
+        match board_to_pheromones(x1, y1, x2, y2) {
+            Some((px, py)) => self.pheromones[px as usize][py as usize],
+            None => 0.0,
+        }
+    }
+
+    // Forgets all trails, used when ants stop exploring
+    pub fn reset_pheromones(&mut self) {
+        for row in &mut self.pheromones {
+            row.fill(MIN_PHEROMONES);
+        }
+    }
+
+    // Forgets pheromones on all edges leading to the field
+    pub fn reset_pheromones_around(&mut self, x: usize, y: usize) {
+        let position = Position { x: x as i32 * WINDOW_TO_GAME_SCALE as i32, y: y as i32 * WINDOW_TO_GAME_SCALE as i32 };
+        for neighbour in self.get_all_neighbors(position) {
+            if let Some((px, py)) = board_to_pheromones(x as i32, y as i32, neighbour.get_x_grid(), neighbour.get_y_grid()) {
+                self.pheromones[px as usize][py as usize] = MIN_PHEROMONES;
+            }
+        }
+    }
+
+    // Unstable tunnels fall in, ants inside die or stay trapped until they dig out
+    fn collapse_tunnels(&mut self) {
+        let collapses = find_collapses(&self.tunnels, &self.soil, &self.mound, self.nest, &self.colony.planned, self.seed, self.tick);
+
+        for (x, y) in collapses {
+            self.set_tunnel(x, y, false);
+            self.rubble[x][y] = true;
+            self.reset_pheromones_around(x, y);
+
+            let (seed, tick) = (self.seed, self.tick);
+            let before = self.ants.len();
+            self.ants.retain(|ant| {
+                let buried = ant.position.get_x_grid() == x as i32 && ant.position.get_y_grid() == y as i32;
+                !buried || StdRng::seed_from_u64(stream_seed(seed, tick, Stream::CollapseKill, ant.id)).gen::<f32>() >= COLLAPSE_KILL_CHANCE
+            });
+
+            let soil = SOIL_TYPES[self.soil[x][y] as usize].name;
+            self.events.push(self.tick, Event::Collapse { x, y, soil, killed: before - self.ants.len() });
+        }
+    }
+
+    // Tunnels nobody uses slowly fill with soil
+    fn decay_tunnels(&mut self) {
+        for (x, y) in find_decayed(&self.tunnels, &self.last_visit, self.nest, &self.colony.planned, self.tick) {
+            self.set_tunnel(x, y, false);
+            self.reset_pheromones_around(x, y);
+            self.decayed += 1;
+        }
+    }
+
+    // Rain floods tunnels and washes pheromones out of them
+    fn update_weather(&mut self) {
+        if let Some(event) = self.weather.start_rain(self.seed, self.tick) {
+            self.events.push(self.tick, event);
+        }
+
+        let (flooded, dried, event) = self.weather.update(&self.tunnels, &self.soil, self.nest, &self.colony.planned);
+        for (x, y) in flooded {
+            self.reset_pheromones_around(x, y);
+            self.mark_changed(x, y);
+        }
+        for (x, y) in dried {
+            self.mark_changed(x, y);
+        }
+        if let Some(event) = event {
+            self.events.push(self.tick, event);
+        }
     }
 
-    // Spawns food in given position
-    fn spawn_food(&mut self, pos: Position) {
+    // Saves tunnels as SVG and graph, noted in the event log
+    pub fn export(&mut self) -> std::io::Result<()> {
+        let path = export::export(self)?;
+        self.events.push(self.tick, Event::Exported { path: path.display().to_string() });
+        Ok(())
+    }
+
+    // Spawns food in given position, returns false if it's too close to the nest
+    pub fn spawn_food(&mut self, mut pos: Position) -> bool {
+
+        // In side view food falls on the surface
+        if SIDE_VIEW {
+            pos.y = (SURFACE_ROW as i32 - 1) * WINDOW_TO_GAME_SCALE as i32;
+        }
 
         let (x_grid, y_grid) = (pos.get_x_grid(), pos.get_y_grid());
 
@@ -138,166 +434,327 @@ impl Terrarium {
         let dy = (self.nest.get_y_grid() - y_grid).abs();
 
         if dx + dy > FOOD_DISTANCE {
-            self.food_sources.push(FoodSource { position: pos, amount: FOOD_AMOUNT_PER_SOURCE, });
+            self.place_food(pos, FOOD_AMOUNT_PER_SOURCE);
+            return true;
         }
+        false
     }
 
-    // Adds food to the nest
-    pub fn add_food(&mut self) {
-        self.food_amount+=1;
+    // Places food source anywhere, used by scenarios
+    pub fn place_food(&mut self, mut pos: Position, amount: usize) {
+        if SIDE_VIEW {
+            pos.y = (SURFACE_ROW as i32 - 1) * WINDOW_TO_GAME_SCALE as i32;
+        }
+        self.food_sources.push(FoodSource { position: pos, amount, diurnal: false, });
     }
-}
 
-// Update and Draw functions called from game engine
-impl ggez::event::EventHandler for Terrarium {
-
-    // Updates every element of game
-    fn update(&mut self, _: &mut Context) -> GameResult {
-        match self.state {
-            GameState::Playing => {
-
-                // Deletes dead food sources
-                self.food_sources.retain(|food| food.amount > 0);
-
-                // Update pheromones
-                for row in &mut self.pheromones {
-                    for pheromone in row.iter_mut() {
-                        if *pheromone > MAX_PHEROMONES / 2.0 {
-                            *pheromone *= EVAPORATION_RATE_FAST;
-                        }
-                        else if *pheromone > MIN_PHEROMONES {
-                            *pheromone *= EVAPORATION_RATE_SLOW;
-                        }
-                        else {
-                            *pheromone = MIN_PHEROMONES;
-                        }
-                    }
-                }
+    // Places or removes obstacle, tunnel under it is filled and trails through it forgotten
+    pub fn set_obstacle(&mut self, x: usize, y: usize, active: bool) {
+        if active {
+            self.set_tunnel(x, y, false);
+            self.reset_pheromones_around(x, y);
+        }
+        if self.obstacles[x][y] != active {
+            self.obstacles[x][y] = active;
+            self.mark_changed(x, y);
+        }
+    }
 
-                // Updates ants - work around borrow checker, function from ChatGPT
-                let mut ants = std::mem::take(&mut self.ants);
-                for ant in &mut ants { ant.update(self); }
+    // Removes food source covering given grid field
+    pub fn remove_food(&mut self, x: i32, y: i32) -> bool {
+        let reach = (FOOD_SCALE / WINDOW_TO_GAME_SCALE / 2.0) as i32;
+        let count = self.food_sources.len();
+        self.food_sources.retain(|food| (food.position.get_x_grid() - x).abs() > reach || (food.position.get_y_grid() - y).abs() > reach);
+        self.food_sources.len() < count
+    }
 
-                // Ants go back to terrarium :)
-                self.ants = ants;
+    // Runs as many ticks as speed says, unless paused
+    pub fn update(&mut self) {
+        if let GameState::Playing = self.state {
+            for _ in 0..self.speed {
+                self.step();
+            }
+        }
+    }
 
-                Ok(())
+    // Commands from keyboard of any front end
+    pub fn control(&mut self, control: Control) {
+        match control {
+            Control::TogglePause => {
+                self.state = match self.state {
+                    GameState::Playing => GameState::Paused,
+                    GameState::Paused => GameState::Playing,
+                };
             }
-            GameState::Paused => {
+            Control::Step => {
+                if let GameState::Paused = self.state {
+                    self.step();
+                }
+            }
+            Control::Faster => self.speed = (self.speed * 2).min(MAX_SPEED),
+            Control::Slower => self.speed = (self.speed / 2).max(1),
+        }
+    }
 
-                // Nothing
+    pub fn set_paused(&mut self, paused: bool) {
+        self.state = if paused { GameState::Paused } else { GameState::Playing };
+    }
 
-                Ok(())
-            }
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, GameState::Paused)
+    }
+
+    // Lines of text describing state of the simulation, shown by front ends
+    pub fn status_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("{}  Tick: {}  Speed: x{}{}", clock(self.tick), self.tick, self.speed, if self.is_paused() { "  Paused" } else { "" }),
+            format!("Ants: {}{}", self.ants.len(), if self.weather.is_raining() { "  Raining" } else { "" }),
+            format!("Excavated: {}  Mound: {}  Refilled: {}  Decayed: {}", self.excavated, self.mound.volume, self.refilled, self.decayed),
+            format!(
+                "Delivered: {}  Food: {}/{}  Brood: {}/{}  Waste: {}/{}  Spilled: {}",
+                self.delivered,
+                self.colony.total(ChamberRole::Storage), self.colony.capacity(ChamberRole::Storage),
+                self.colony.total(ChamberRole::Brood), self.colony.capacity(ChamberRole::Brood),
+                self.colony.total(ChamberRole::Midden), self.colony.capacity(ChamberRole::Midden),
+                self.colony.spilled,
+            ),
+        ];
+        let traits: Vec<String> = ["Alpha", "Beta", "Soil limit"]
+            .iter()
+            .zip(self.trait_distribution())
+            .map(|(label, (_, mean, deviation))| format!("{}: {:.2}±{:.2}", label, mean, deviation))
+            .collect();
+        lines.push(traits.join("  "));
+        if let Some(sample) = self.bridge.as_ref().and_then(BridgeMonitor::latest) {
+            let fraction = sample.fraction.map_or("-".to_string(), |fraction| format!("{:.0}%", 100.0 * fraction));
+            lines.push(format!("Upper branch: {} (model {:.0}%)  Crossings: {}/{}", fraction, 100.0 * sample.model, sample.crossings[0], sample.crossings[1]));
+        }
+        if let Some((ratio, trails, foods)) = self.latest_optimality() {
+            lines.push(format!("Trail/optimal: {:.2} ({}/{} foods)", ratio, trails, foods));
         }
+        lines.extend(self.events.latest(EVENT_LOG_LINES));
+        lines
     }
 
-    // After update, this function is called to draw everything
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+    // Mean and standard deviation of alpha, beta and soil limit over all ants
+    pub fn trait_distribution(&self) -> Vec<(&'static str, f32, f32)> {
+        let count = self.ants.len().max(1) as f32;
+        let values: Vec<Params> = self.ants.iter().map(|ant| ant.params(self)).collect();
+
+        Traits::NAMES
+            .iter()
+            .map(|&name| {
+                let mean = values.iter().map(|params| params.get(name).unwrap()).sum::<f32>() / count;
+                let variance = values.iter().map(|params| (params.get(name).unwrap() - mean).powi(2)).sum::<f32>() / count;
+                (name, mean, variance.sqrt())
+            })
+            .collect()
+    }
+
+    // Average trail to optimal ratio from the last measurement, food sources with trail and all food sources
+    pub fn latest_optimality(&self) -> Option<(f32, usize, usize)> {
+        let last = self.optimality.last()?.tick;
+        let start = self.optimality.iter().rposition(|sample| sample.tick != last).map_or(0, |index| index + 1);
+        let latest = &self.optimality[start..];
+        optimality::mean_ratio(latest).map(|(ratio, trails)| (ratio, trails, latest.len()))
+    }
 
-        // Create Canvas to draw on
-        let mut canvas = Canvas::from_frame(ctx, Color::new(0.22, 0.15, 0.13, 1.0));
+    // One tick of the simulation
+    pub fn step(&mut self) {
 
-        // Render tunnels
-        for (x, row) in self.tunnels.iter().enumerate() {
-            for (y, &active) in row.iter().enumerate() {
-                if active {
-                    let position = [(x as f32) * WINDOW_TO_GAME_SCALE, (y as f32) * WINDOW_TO_GAME_SCALE];
-                    canvas.draw(&self.pre_rendered_tunnel, DrawParam::default().dest(position));
+        // Deletes dead food sources
+        self.food_sources.retain(|food| food.amount > 0);
+
+        // Planned changes of the world happen before anyone moves
+        let mut scenario = std::mem::take(&mut self.scenario);
+        scenario.run(self);
+        self.scenario = scenario;
+
+        // Rain before evaporation, so flooded edges stay at minimum
+        self.update_weather();
+
+        // Update pheromones, they evaporate faster when it's warm
+        let (rate_fast, rate_slow) = (evaporation_rate(self.params.evaporation_rate_fast, self.tick), evaporation_rate(self.params.evaporation_rate_slow, self.tick));
+        self.pheromones.par_iter_mut().for_each(|row| {
+            for pheromone in row.iter_mut() {
+                if *pheromone > MAX_PHEROMONES / 2.0 {
+                    *pheromone *= rate_fast;
+                }
+                else if *pheromone > MIN_PHEROMONES {
+                    *pheromone *= rate_slow;
+                }
+                else {
+                    *pheromone = MIN_PHEROMONES;
                 }
             }
-        }
+        });
 
-        // Render pheromones above visibility limit
-        for (x, row) in self.pheromones.iter_mut().enumerate() {
-            for (y, pheromone) in row.iter().enumerate() {
-                if *pheromone > MIN_PHEROMONES {
-                    if let Some((mut x1, mut y1, mut x2, mut y2)) = pheromones_to_board(x as i32, y as i32) {
-                        // Upscale values
-                        x1 *= WINDOW_TO_GAME_SCALE as i32;
-                        y1 *= WINDOW_TO_GAME_SCALE as i32;
-                        x2 *= WINDOW_TO_GAME_SCALE as i32;
-                        y2 *= WINDOW_TO_GAME_SCALE as i32;
-
-                        // Average point to render in half of 2 pieces of tunnel
-                        let (px, py) = ((x1 + x2) / 2, (y1 + y2) / 2);
-
-                        // Render
-                        let pheromone_mesh = predefined_rectangle_mesh(ctx, PHEROMONE_SIZE, Color::new(1.0, 1.0, 1.0, *pheromone/MAX_PHEROMONES))?;
-                        canvas.draw(&pheromone_mesh, DrawParam::default().dest([px as f32, py as f32]));
-                    }
-                }
+        // Updates ants - work around borrow checker, function from ChatGPT
+        let mut ants = std::mem::take(&mut self.ants);
+
+        // Ants decide in parallel, each with its own random generator seeded by tick and id
+        let world: &Terrarium = self;
+        let intents: Vec<Vec<Intent>> = ants
+            .par_iter_mut()
+            .map(|ant| {
+                let mut rng = StdRng::seed_from_u64(stream_seed(world.seed, world.tick, Stream::Decide, ant.id));
+                ant.decide(world, &mut rng)
+            })
+            .collect();
+
+        // Fields ants stand on count as visited before anyone fills dead ends, so nobody is buried alive
+        for ant in &ants {
+            let (x, y) = (ant.position.get_x_grid(), ant.position.get_y_grid());
+            if x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT {
+                self.last_visit[x as usize][y as usize] = self.tick;
             }
         }
 
-        // Render ants
-        for ant in &self.ants {
-            ant.draw(ctx, &mut canvas)?;
+        // Decisions are applied in order of ants, so result doesn't depend on threads
+        for (ant, ant_intents) in ants.iter_mut().zip(intents) {
+            ant.commit(self, ant_intents);
+            self.record_visit(ant.position);
         }
 
-        // Render nest
-        let nest_square = predefined_rectangle_mesh(ctx, NEST_SIZE, Color::new(0.141, 0.090, 0.078, 1.0))?;
-        let nest_pos = [self.nest.x-(NEST_SIZE/2.0) as i32  , self.nest.y-(NEST_SIZE/2.0) as i32];
-        canvas.draw(&nest_square, DrawParam::default().dest([nest_pos[0] as f32, nest_pos[1] as f32]));
-        // Render text on nest
-        let text = Text::new(TextFragment {
-            text: format!("{}", self.food_amount),
-            color: Some(Color::WHITE),
-            scale: Some(PxScale::from(15.0)),
-            ..Default::default()
-        });
-        let dimensions = text.dimensions(ctx);
-        let text_pos = Point2 {
-            x: (nest_pos[0] as f32 + NEST_SIZE / 2.0 - dimensions.unwrap().w / 2.0).round(),
-            y: (nest_pos[1] as f32 + NEST_SIZE / 2.0 - dimensions.unwrap().h/2.0).round(),
-        };
-        canvas.draw(&text, DrawParam::default().dest(text_pos));
-
-        // Render food sources
-        for food in &self.food_sources {
-            if food.amount > 0 {
-                let food_size = FOOD_SCALE;
-                let pos = [food.position.x-(FOOD_SCALE/2.0) as i32, food.position.y-(FOOD_SCALE/2.0) as i32];
-                let square = predefined_rectangle_mesh(ctx, food_size, Color::GREEN)?;
-                canvas.draw(&square, DrawParam::default().dest([pos[0] as f32, pos[1] as f32]), );
-
-                // Render text on food
-                let text = Text::new(TextFragment {
-                    text: format!("{}", food.amount),
-                    color: Some(Color::BLACK),
-                    scale: Some(PxScale::from(25.0)),
-                    ..Default::default()
-                });
-                let dimensions = text.dimensions(ctx);
-                let text_pos = Point2 {
-                    x: (pos[0] as f32 + food_size / 2.0 - dimensions.unwrap().w / 2.0).round(),
-                    y: (pos[1] as f32 + food_size / 2.0 - dimensions.unwrap().h/2.0).round(),
-                };
-                canvas.draw(&text, DrawParam::default().dest(text_pos));
-            }
+        // Ants without support fall
+        if SIDE_VIEW {
+            for ant in &mut ants { ant.apply_gravity(self); }
         }
 
-        //Finish drawing
-        canvas.finish(ctx)?;
-        Ok(())
+        // Ants go back to terrarium :)
+        self.ants = ants;
+        if let Some(bridge) = &mut self.bridge {
+            bridge.update(&self.ants, self.tick);
+        }
+        self.hatch_larvae();
+
+        // Check stability of tunnels from time to time
+        if !self.fixed_tunnels && self.tick.is_multiple_of(STABILITY_INTERVAL) {
+            self.collapse_tunnels();
+            self.decay_tunnels();
+        }
+
+        // Compare trails with shortest paths from time to time
+        if OPTIMALITY_INTERVAL != 0 && self.tick.is_multiple_of(OPTIMALITY_INTERVAL) {
+            self.optimality.extend(optimality::measure(self));
+        }
+        self.tick += 1;
     }
 
-    // Adding food by click
-    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> Result<(), GameError> {
-        match button {
-            MouseButton::Left => Ok(self.spawn_food(Position { x: x as i32, y: y as i32})),
-            _ => Ok(())
+    // Puts food brought by ant into storage chamber, larvae will inherit traits of the forager
+    pub fn deliver_food(&mut self, forager: Traits) {
+        self.colony.store_food(self.nest);
+        self.delivered += 1;
+
+        if self.foragers.len() == TRAIT_POOL.max(1) {
+            self.foragers.pop_front();
         }
+        self.foragers.push_back(forager);
     }
 
-    // Play/Pause handler - function from ChatGPT
-    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeated: bool) -> Result<(), GameError> {
-        if input.keycode == Some(KeyCode::Space) {
-            self.state = match self.state {
-                GameState::Playing => GameState::Paused,
-                GameState::Paused => GameState::Playing,
-            };
+    // Digs one field of chamber waiting for it, soil goes straight to the mound
+    pub fn dig_chamber(&mut self, site: usize) {
+        if let Some((x, y)) = self.colony.field_to_dig() {
+            // Chamber is dug around obstacles
+            if !self.obstacles[x][y] && self.dig_tunnel(x, y) {
+                self.dump_soil(site, 1);
+            }
+            self.colony.field_dug();
         }
-        Ok(())
+    }
+
+    // Grown larvae become new ants
+    fn hatch_larvae(&mut self) {
+        let hatched = self.colony.raise_brood(self.nest, self.tick);
+
+        for _ in 0..hatched {
+            if self.ants.len() >= MAX_ANTS {
+                break;
+            }
+            let id = self.next_ant_id;
+            let mut rng = StdRng::seed_from_u64(stream_seed(self.seed, self.tick, Stream::Hatch, id));
+            let mut ant = Ant::new(id, caste_of(id), &self.nest, &mut rng);
+
+            // Parent is one of latest successful foragers, founding colony without them stays as it is
+            if !self.foragers.is_empty() {
+                ant.traits = self.foragers[rng.gen_range(0..self.foragers.len())].mutated(&mut rng);
+            }
+            self.ants.push(ant);
+            self.next_ant_id += 1;
+        }
+    }
+}
+
+// Castes get their share of ants in order, pattern repeats for ants born later
+fn caste_of(id: u64) -> usize {
+    let total_share: f32 = CASTES.iter().map(|(_, share)| share).sum();
+    let point = ((id % ANT_COUNT as u64) as f32 + 0.5) / ANT_COUNT as f32 * total_share;
+
+    let mut caste = 0;
+    let mut cumulative_share = CASTES[0].1;
+    while cumulative_share < point && caste + 1 < CASTES.len() {
+        caste += 1;
+        cumulative_share += CASTES[caste].1;
+    }
+    caste
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(x: i32, y: i32) -> Position {
+        Position { x: x * WINDOW_TO_GAME_SCALE as i32, y: y * WINDOW_TO_GAME_SCALE as i32 }
+    }
+
+    // Ant carrying soil home, standing in straight tunnel from (10, 100) to (14, 100) dug in given tick
+    fn corridor_with_returning_ant(tick: u64) -> (Terrarium, Ant) {
+        let mut terrarium = Terrarium::with_seed(1).unwrap();
+        terrarium.tick = tick;
+        for x in 9..=15 {
+            for y in 99..=101 {
+                terrarium.set_obstacle(x, y, false);
+                terrarium.set_tunnel(x, y, false);
+            }
+        }
+        for x in 10..=14 {
+            terrarium.dig_tunnel(x, 100);
+        }
+
+        let mut ant = Ant::new(0, 0, &field(13, 100), &mut StdRng::seed_from_u64(0));
+        ant.position = field(13, 100);
+        ant.returning = true;
+        ant.soil_carried = 2;
+        (terrarium, ant)
+    }
+
+    #[test]
+    fn tip_being_dug_is_not_filled() {
+        let (mut terrarium, mut ant) = corridor_with_returning_ant(1000);
+        terrarium.tick += DEAD_END_MIN_AGE;
+        ant.commit(&mut terrarium, vec![Intent::Fill(field(14, 100))]);
+        assert!(terrarium.tunnels[14][100]);
+        assert_eq!(ant.soil_carried, 2);
+
+        // Digger came back through the tip
+        terrarium.tick += 1;
+        terrarium.record_visit(field(14, 100));
+        ant.commit(&mut terrarium, vec![Intent::Fill(field(14, 100))]);
+        assert!(terrarium.tunnels[14][100]);
+    }
+
+    #[test]
+    fn abandoned_dead_end_is_filled() {
+        let (mut terrarium, mut ant) = corridor_with_returning_ant(1000);
+        terrarium.tick += DEAD_END_MIN_AGE + 1;
+        ant.commit(&mut terrarium, vec![Intent::Fill(field(14, 100))]);
+        assert!(!terrarium.tunnels[14][100]);
+        assert_eq!(ant.soil_carried, 1);
+
+        // Middle of the tunnel isn't a dead end
+        ant.commit(&mut terrarium, vec![Intent::Fill(field(12, 100))]);
+        assert!(terrarium.tunnels[12][100]);
+        assert_eq!(ant.soil_carried, 1);
     }
 }
@@ -0,0 +1,122 @@
+use std::io::{self, Write};
+use std::time::Duration;
+use crossterm::{cursor, execute, queue, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, MouseButton, MouseEventKind};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crate::config::*;
+use crate::functions::*;
+use crate::raster::rasterise;
+#[cfg(feature = "record")]
+use crate::recorder::{self, Recorder};
+#[cfg(feature = "server")]
+use crate::server::Server;
+use crate::terrarium::Terrarium;
+
+// Terminal front end, works over SSH without any display server
+pub fn run(mut terrarium: Terrarium) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide, event::EnableMouseCapture)?;
+
+    let result = main_loop(&mut terrarium, &mut stdout);
+
+    // Give terminal back even if drawing failed
+    execute!(stdout, event::DisableMouseCapture, ResetColor, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn main_loop(terrarium: &mut Terrarium, stdout: &mut impl Write) -> io::Result<()> {
+    let mut stride = 1; // Grid fields per character in the last drawn frame
+
+    #[cfg(feature = "record")]
+    let mut recorder = if has_flag("--record") { Some(Recorder::start().map_err(io::Error::other)?) } else { None };
+    #[cfg(feature = "server")]
+    let mut server = if has_flag("--server") { Some(Server::start()?) } else { None };
+
+    loop {
+
+        // Keys and clicks mirror the window front end, q and Esc quit
+        if event::poll(Duration::from_millis(TUI_FRAME_MS))? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char(' ') => terrarium.control(Control::TogglePause),
+                    KeyCode::Char('s') => terrarium.control(Control::Step),
+                    KeyCode::Char('+') | KeyCode::Char('=') => terrarium.control(Control::Faster),
+                    KeyCode::Char('-') => terrarium.control(Control::Slower),
+                    KeyCode::Char('e') => terrarium.export()?,
+                    #[cfg(feature = "record")]
+                    KeyCode::Char('r') => recorder::toggle(&mut recorder).map_err(io::Error::other)?,
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    _ => {}
+                },
+                Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                    let x = mouse.column as usize * stride;
+                    let y = mouse.row as usize * 2 * stride;
+                    let scale = WINDOW_TO_GAME_SCALE as usize;
+                    terrarium.spawn_food(Position { x: (x * scale) as i32, y: (y * scale) as i32 });
+                }
+                _ => {}
+            }
+        }
+
+        #[cfg(feature = "server")]
+        if let Some(server) = &mut server {
+            server.poll(terrarium);
+        }
+
+        terrarium.update();
+
+        #[cfg(feature = "server")]
+        if let Some(server) = &mut server {
+            server.broadcast(terrarium);
+        }
+
+        #[allow(unused_mut)]
+        let mut status = terrarium.status_lines();
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &mut recorder {
+            recorder.capture(terrarium).map_err(io::Error::other)?;
+            status.push(recorder.status());
+        }
+
+        stride = draw(terrarium, &status, stdout)?;
+    }
+}
+
+// Shrinks the grid if terminal is too small, status lines stay under it
+fn stride(status_lines: usize) -> io::Result<usize> {
+    let (columns, rows) = terminal::size()?;
+    let map_rows = (rows as usize).saturating_sub(status_lines).max(1);
+    Ok(WIDTH.div_ceil(columns.max(1) as usize).max(HEIGHT.div_ceil(2 * map_rows)).max(1))
+}
+
+// Draws two fields in every character - upper one as foreground of half-block, lower one as background
+fn draw(terrarium: &Terrarium, status: &[String], stdout: &mut impl Write) -> io::Result<usize> {
+    let pixels = rasterise(terrarium);
+    let stride = stride(status.len())?;
+
+    queue!(stdout, cursor::MoveTo(0, 0))?;
+    for row in 0..HEIGHT.div_ceil(2 * stride) {
+        for column in 0..WIDTH.div_ceil(stride) {
+            let x = column * stride;
+            let (upper_y, lower_y) = (2 * row * stride, (2 * row + 1) * stride);
+            let (r, g, b) = pixels[upper_y * WIDTH + x];
+            queue!(stdout, SetForegroundColor(Color::Rgb { r, g, b }))?;
+            if lower_y < HEIGHT {
+                let (r, g, b) = pixels[lower_y * WIDTH + x];
+                queue!(stdout, SetBackgroundColor(Color::Rgb { r, g, b }))?;
+            } else {
+                queue!(stdout, ResetColor)?;
+            }
+            queue!(stdout, Print('▀'))?;
+        }
+        queue!(stdout, ResetColor, Print("\r\n"))?;
+    }
+
+    for line in status {
+        queue!(stdout, terminal::Clear(terminal::ClearType::CurrentLine), Print(line), Print("\r\n"))?;
+    }
+    stdout.flush()?;
+    Ok(stride)
+}
@@ -0,0 +1,141 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use crate::config::*;
+use crate::functions::*;
+use crate::terrarium::Terrarium;
+
+// Parameter set with its average fitness over all seeded runs
+#[derive(Clone, Copy)]
+struct Candidate {
+    params: Params,
+    fitness: f32,
+}
+
+// What tuning tries to maximise, measured at the end of one run
+fn fitness(terrarium: &Terrarium) -> f32 {
+    match TUNE_FITNESS {
+        "food_per_soil" => terrarium.delivered as f32 / terrarium.excavated.max(1) as f32,
+        _ => terrarium.delivered as f32 / terrarium.tick.max(1) as f32,
+    }
+}
+
+// Runs prepared world headless for TUNE_TICKS, without recording or stop conditions, and returns its fitness
+pub fn score(mut terrarium: Terrarium) -> f32 {
+    while terrarium.tick < TUNE_TICKS {
+        terrarium.step();
+        if !UNLIMITED_FOOD && terrarium.food_sources.iter().all(|food| food.amount == 0) {
+            break;
+        }
+    }
+    fitness(&terrarium)
+}
+
+fn evaluate_run(params: Params, seed: u64) -> io::Result<f32> {
+    let mut terrarium = Terrarium::with_seed(seed)?;
+    terrarium.params = params;
+    Ok(score(terrarium))
+}
+
+// Every candidate meets the same worlds, so they are compared fairly
+fn evaluate(population: &[Params]) -> io::Result<Vec<Candidate>> {
+    let runs: Vec<(usize, u64)> = (0..population.len()).flat_map(|index| (0..TUNE_SEEDS).map(move |run| (index, run))).collect();
+    let results: Vec<f32> = runs.par_iter().map(|&(index, run)| evaluate_run(population[index], SEED + run)).collect::<io::Result<_>>()?;
+
+    Ok(population
+        .iter()
+        .enumerate()
+        .map(|(index, &params)| {
+            let total: f32 = results[index * TUNE_SEEDS as usize..(index + 1) * TUNE_SEEDS as usize].iter().sum();
+            Candidate { params, fitness: total / TUNE_SEEDS as f32 }
+        })
+        .collect())
+}
+
+// Random parameters inside tuned ranges, others keep values from config
+fn random_params(rng: &mut StdRng) -> Params {
+    let mut params = Params::default();
+    for &(name, min, max) in TUNE_RANGES {
+        params.set(name, rng.gen_range(min..=max)).unwrap();
+    }
+    params
+}
+
+// Best of few randomly picked candidates
+fn tournament<'a>(candidates: &'a [Candidate], rng: &mut StdRng) -> &'a Candidate {
+    (0..TUNE_TOURNAMENT.max(1))
+        .map(|_| &candidates[rng.gen_range(0..candidates.len())])
+        .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+        .unwrap()
+}
+
+// Every parameter comes from one of the parents and sometimes moves a little, staying inside its range
+fn offspring(first: &Params, second: &Params, rng: &mut StdRng) -> Params {
+    let mut params = *first;
+    for &(name, min, max) in TUNE_RANGES {
+        let mut value = if rng.gen::<bool>() { first.get(name) } else { second.get(name) }.unwrap();
+        if rng.gen::<f32>() < TUNE_MUTATION_RATE {
+            value += rng.gen_range(-1.0..=1.0) * TUNE_MUTATION_SIZE * (max - min);
+        }
+        params.set(name, value.clamp(min, max)).unwrap();
+    }
+    params
+}
+
+// Genetic algorithm over runtime parameters, writes best config and fitness history into TUNE_DIR
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    fs::create_dir_all(TUNE_DIR)?;
+
+    // Values from config compete too, so tuning never ends worse than them
+    let mut population: Vec<Params> = vec![Params::default()];
+    while population.len() < TUNE_POPULATION.max(2) {
+        population.push(random_params(&mut rng));
+    }
+
+    let mut history = String::from("generation,best,mean,worst");
+    for &(name, _, _) in TUNE_RANGES {
+        write!(history, ",{}", name)?;
+    }
+    history.push('\n');
+
+    let mut best: Option<Candidate> = None;
+    for generation in 0..TUNE_GENERATIONS {
+        let mut candidates = evaluate(&population)?;
+        candidates.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+
+        let leader = candidates[0];
+        if best.is_none_or(|best| leader.fitness > best.fitness) {
+            best = Some(leader);
+        }
+
+        let mean = candidates.iter().map(|candidate| candidate.fitness).sum::<f32>() / candidates.len() as f32;
+        write!(history, "{},{},{},{}", generation, leader.fitness, mean, candidates[candidates.len() - 1].fitness)?;
+        for &(name, _, _) in TUNE_RANGES {
+            write!(history, ",{}", leader.params.get(name).unwrap())?;
+        }
+        history.push('\n');
+        println!("Generation {}: best {:.5}, mean {:.5}", generation, leader.fitness, mean);
+
+        // Elite survives, rest are children of tournament winners
+        population = candidates.iter().take(TUNE_ELITE).map(|candidate| candidate.params).collect();
+        while population.len() < candidates.len() {
+            let (first, second) = (tournament(&candidates, &mut rng).params, tournament(&candidates, &mut rng).params);
+            population.push(offspring(&first, &second, &mut rng));
+        }
+    }
+
+    let directory = PathBuf::from(TUNE_DIR);
+    fs::write(directory.join("history.csv"), history)?;
+    if let Some(best) = best {
+        let config = format!("# {} = {}\n{}", TUNE_FITNESS, best.fitness, best.params.to_config());
+        fs::write(directory.join("best.cfg"), config)?;
+        println!("Best {} {:.5}, saved to {} (run with --params)", TUNE_FITNESS, best.fitness, directory.join("best.cfg").display());
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use crate::config::*;
+use crate::events::Event;
+use crate::functions::*;
+use crate::stability::is_protected;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+
+// Grid fields as (x, y)
+type Fields = Vec<(usize, usize)>;
+
+// Rain and water standing in tunnels
+pub struct Weather {
+    pub water: Vec<Vec<f32>>, // Water level of every field, flooded field can't be entered
+    rain_left: u64,           // Ticks until rain stops
+    intensity: f32,           // Water added per tick
+    pub random_rain: bool,    // Rains besides planned ones, experiments turn them off
+}
+
+impl Weather {
+
+    pub fn new() -> Self {
+        Weather { water: vec![vec![0.0; HEIGHT]; WIDTH], rain_left: 0, intensity: 0.0, random_rain: true }
+    }
+
+    pub fn is_raining(&self) -> bool {
+        self.rain_left > 0
+    }
+
+    pub fn is_flooded(&self, x: usize, y: usize) -> bool {
+        self.water[x][y] > 0.0
+    }
+
+    // Starts rain planned for this tick or random one, returns event to be logged
+    pub fn start_rain(&mut self, seed: u64, tick: u64) -> Option<Event> {
+        if self.is_raining() {
+            return None;
+        }
+
+        let planned = RAIN_SCHEDULE.iter().find(|(start, _, _)| *start == tick);
+        let (duration, intensity) = match planned {
+            Some(&(_, duration, intensity)) => (duration, intensity),
+            None if !self.random_rain => return None,
+            None => {
+                let mut rng = StdRng::seed_from_u64(stream_seed(seed, tick, Stream::Rain, 0));
+                if rng.gen::<f32>() >= RAIN_CHANCE {
+                    return None;
+                }
+                (RAIN_DURATION, RAIN_INTENSITY)
+            }
+        };
+
+        self.rain_left = duration;
+        self.intensity = intensity;
+        Some(Event::RainStarted { duration, intensity })
+    }
+
+    // Rain floods tunnels close to nest (top-down) or surface (side view), but not the nest and its chambers,
+    // water drains into soil
+    // Returns flooded fields, fields which dried up this tick and event if rain stopped
+    pub fn update(&mut self, tunnels: &[Vec<bool>], soil: &[Vec<u8>], nest: Position, chambers: &[Vec<bool>]) -> (Fields, Fields, Option<Event>) {
+        let rain = if self.is_raining() { self.intensity } else { 0.0 };
+
+        let (flooded, dried): (Vec<_>, Vec<_>) = self.water
+            .par_iter_mut()
+            .enumerate()
+            .map(|(x, column)| {
+                let (mut flooded, mut dried) = (Vec::new(), Vec::new());
+                for (y, water) in column.iter_mut().enumerate() {
+                    let was_wet = *water > 0.0;
+                    let reach = if SIDE_VIEW {
+                        y as i32 - SURFACE_ROW as i32
+                    } else {
+                        (x as i32 - nest.get_x_grid()).abs() + (y as i32 - nest.get_y_grid()).abs()
+                    };
+                    if tunnels[x][y] && reach < RAIN_REACH && !is_protected(nest, chambers, x, y) {
+                        *water = (*water + rain).min(1.0);
+                    }
+
+                    *water = (*water - SOIL_TYPES[soil[x][y] as usize].drain_rate).max(0.0);
+                    if *water > 0.0 {
+                        flooded.push((x, y));
+                    } else if was_wet {
+                        dried.push((x, y));
+                    }
+                }
+                (flooded, dried)
+            })
+            .unzip();
+
+        // Count down the rain
+        let mut event = None;
+        if self.is_raining() {
+            self.rain_left -= 1;
+            if !self.is_raining() {
+                event = Some(Event::RainStopped);
+            }
+        }
+
+        (flooded.into_iter().flatten().collect(), dried.into_iter().flatten().collect(), event)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rain_floods_tunnels_but_not_nest_and_chambers() {
+        let nest = Position { x: 90 * WINDOW_TO_GAME_SCALE as i32, y: 60 * WINDOW_TO_GAME_SCALE as i32 };
+        let mut tunnels = vec![vec![false; HEIGHT]; WIDTH];
+        let mut chambers = vec![vec![false; HEIGHT]; WIDTH];
+        tunnels[90][50..=70].fill(true);
+        chambers[90][52] = true;
+
+        let mut weather = Weather::new();
+        weather.rain_left = 10;
+        weather.intensity = 0.5;
+        let (flooded, _, _) = weather.update(&tunnels, &vec![vec![0; HEIGHT]; WIDTH], nest, &chambers);
+
+        // Side view measures reach from the surface instead
+        if !SIDE_VIEW {
+            assert!(flooded.contains(&(90, 53)));
+            assert!(flooded.contains(&(90, 70)));
+        }
+        assert!(!flooded.contains(&(90, 60)));
+        assert!(!flooded.contains(&(90, 52)));
+        assert!(flooded.iter().all(|&(x, y)| tunnels[x][y] && !is_protected(nest, &chambers, x, y)));
+    }
+}